@@ -0,0 +1,31 @@
+#![feature(test)]
+extern crate test;
+use ghoti_cpu::bot::{BeamSearchAI, PlayerState, AI};
+use puyoai::{color::PuyoColor, field::CoreField, kumipuyo::Kumipuyo};
+
+/// `(depth, width) = (40, 140)` で 1 回思考する
+/// 展開した局面の数を `b.bytes` に入れているので、`MB/s` が「100 万局面 / 秒」になる
+#[bench]
+fn bench_beam_search_40_140(b: &mut test::Bencher) {
+    let seq = vec![
+        Kumipuyo::new(PuyoColor::RED, PuyoColor::BLUE),
+        Kumipuyo::new(PuyoColor::YELLOW, PuyoColor::GREEN),
+    ];
+    let mut player_state = PlayerState::initial_state(seq, None);
+    player_state.tumo_index = 10;
+    player_state.field = CoreField::from_str(concat!(
+        "B.....", // 3
+        "RY..G.", // 2
+        "RRYBBG"  // 1
+    ));
+
+    let ai = BeamSearchAI::new().with_seed(0);
+    let nodes = ai
+        .think_with_parameters(player_state.clone(), None, 40, 140, 1)
+        .statistics
+        .unwrap()
+        .nodes;
+    b.bytes = nodes as u64;
+
+    b.iter(|| test::black_box(ai.think_with_parameters(player_state.clone(), None, 40, 140, 1)));
+}
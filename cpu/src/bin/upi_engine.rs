@@ -0,0 +1,64 @@
+//! 標準入出力で UPI を話すエンジン
+//! （UPI: https://github.com/TukamotoRyuzo/upi-protocol/blob/master/README.md）
+
+use std::io::{stdin, stdout, BufRead, Write};
+
+use ghoti_cpu::{
+    bot::{BeamSearchAI, OpponentView, AI},
+    upi::{parse_upi_command, pfen::format_move, UpiCommand, UpiPosition},
+};
+
+fn main() -> Result<(), std::io::Error> {
+    let ai = BeamSearchAI::new();
+
+    let stdin = stdin();
+    let mut stdout = stdout();
+
+    let mut position: Option<UpiPosition> = None;
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_upi_command(&line) {
+            Ok(UpiCommand::Upi) => {
+                writeln!(stdout, "id name ghoti ({})", ai.name())?;
+                writeln!(stdout, "id author morioprog")?;
+                writeln!(stdout, "upiok")?;
+            }
+            Ok(UpiCommand::IsReady) => writeln!(stdout, "readyok")?,
+            Ok(UpiCommand::UpiNewGame) => position = None,
+            Ok(UpiCommand::Position(new_position)) => position = Some(new_position),
+            Ok(UpiCommand::Go) => match &position {
+                Some(position) => {
+                    let ai_decision = ai.think(
+                        position.player_state_1p.clone(),
+                        position.player_state_2p.as_ref().map(OpponentView::from),
+                        None,
+                    );
+                    writeln!(
+                        stdout,
+                        "info time {} string {}",
+                        ai_decision.elapsed.as_millis(),
+                        ai_decision.log_output.replace('\n', " ")
+                    )?;
+                    match ai_decision.decisions.first() {
+                        Some(decision) => writeln!(stdout, "bestmove {}", format_move(decision))?,
+                        None => writeln!(stdout, "info string no move is found")?,
+                    }
+                }
+                None => writeln!(stdout, "info string no position is given")?,
+            },
+            // 思考は同期的に行っているので、`stop` が来る時点で思考は終わっている
+            Ok(UpiCommand::Stop) => {}
+            Ok(UpiCommand::Quit) => break,
+            Err(e) => writeln!(stdout, "info string {}", e)?,
+        }
+
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
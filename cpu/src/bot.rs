@@ -1,8 +1,15 @@
 pub mod ai;
+pub mod ai_spec;
 
 pub mod beam_search_ai;
+pub mod external_ai;
 pub mod random_ai;
 
-pub use ai::{AIDecision, PlayerState, AI};
-pub use beam_search_ai::beam_search_ai::BeamSearchAI;
+pub use ai::{AIDecision, DecisionReason, OpponentView, PlayerState, SearchStatistics, AI};
+pub use ai_spec::{new_ai, AIContext, AIFactory, AIRegistry, AISpec};
+pub use beam_search_ai::{
+    beam_search_ai::{derive_seed, BeamSearchAI},
+    fire_strategy::{DefaultFireStrategy, FireStrategy, FireThresholds},
+};
+pub use external_ai::external_ai::ExternalAI;
 pub use random_ai::random_ai::RandomAI;
@@ -1,6 +1,9 @@
 use std::{time::Duration, vec::Vec};
 
-use puyoai::{decision::Decision, field::core_field::CoreField, kumipuyo::kumipuyo::Kumipuyo};
+use puyoai::{
+    decision::Decision, es_frame, field::core_field::CoreField, kumipuyo::kumipuyo::Kumipuyo,
+};
+use serde::{Deserialize, Serialize};
 
 pub trait AI {
     fn new() -> Self
@@ -10,7 +13,7 @@ pub trait AI {
     fn think(
         &self,
         player_state_1p: PlayerState,
-        player_state_2p: Option<PlayerState>,
+        player_state_2p: Option<OpponentView>,
         think_frame: Option<usize>,
     ) -> AIDecision;
 }
@@ -20,6 +23,10 @@ pub struct AIDecision {
     pub decisions: Vec<Decision>,
     pub log_output: String,
     pub elapsed: Duration,
+    /// その手を選んだ理由
+    pub reason: DecisionReason,
+    /// 探索の統計（探索しない AI なら `None`）
+    pub statistics: Option<SearchStatistics>,
 }
 
 impl AIDecision {
@@ -28,22 +35,79 @@ impl AIDecision {
             decisions,
             log_output,
             elapsed,
+            reason: DecisionReason::Unknown,
+            statistics: None,
         }
     }
     pub fn from_decision(decision: &Decision, log_output: String, elapsed: Duration) -> Self {
-        AIDecision {
-            decisions: vec![decision.clone()],
-            log_output,
-            elapsed,
-        }
+        AIDecision::new(vec![decision.clone()], log_output, elapsed)
     }
     pub fn zero() -> Self {
-        AIDecision {
-            decisions: vec![Decision::new(0, 0)],
-            log_output: "".to_string(),
-            elapsed: Duration::ZERO,
-        }
+        AIDecision::new(vec![Decision::new(0, 0)], "".to_string(), Duration::ZERO)
     }
+
+    pub fn with_reason(mut self, reason: DecisionReason) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    pub fn with_statistics(mut self, statistics: SearchStatistics) -> Self {
+        self.statistics = Some(statistics);
+        self
+    }
+
+    /// 連鎖を発火する手か
+    pub fn is_fire(&self) -> bool {
+        matches!(self.reason, DecisionReason::Fire { .. })
+    }
+}
+
+/// AI がその手を選んだ理由
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DecisionReason {
+    /// 序盤のテンプレ
+    Opening,
+    /// 連鎖を発火する
+    Fire {
+        chain: usize,
+        score: usize,
+        /// 発火時のツモ以外の操作に必要なフレーム数
+        frame_control: usize,
+        /// 連鎖が終わるまでのフレーム数
+        frame_chain: usize,
+    },
+    /// 評価値が最も高い形を組む
+    Build { eval_score: i32 },
+    /// どこに置いても死ぬ
+    ForcedDeath,
+    /// ランダムに置く
+    Random,
+    /// 外部の AI が選んだ
+    External,
+    /// 外部の AI が答えられなかったので、代わりに置いた
+    Fallback,
+    /// 理由が分からない（記録されていない古い棋譜など）
+    #[default]
+    Unknown,
+}
+
+/// 探索の統計
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchStatistics {
+    /// 最後まで読み切った探索の深さ
+    pub depth: usize,
+    /// ビーム幅
+    pub width: usize,
+    /// モンテカルロの試行回数
+    pub parallel: usize,
+    /// 展開した局面の数（全試行の合計）
+    pub nodes: usize,
+    /// 選んだ初手が、何回の試行で最善だったか
+    pub votes: usize,
+    /// 同じ盤面としてまとめた局面の数（全試行の合計）
+    #[serde(default)]
+    pub transpositions: usize,
 }
 
 #[derive(Clone)]
@@ -66,6 +130,10 @@ pub struct PlayerState {
     pub current_chain: usize,
     /// ツモ番号
     pub tumo_index: usize,
+    /// 現在のおじゃまぷよのレート（マージンタイムにより下がっていく）
+    pub ojama_rate: usize,
+    /// 全消しボーナスを持っているか（次の連鎖で `carry_over` に加算される）
+    pub has_zenkeshi: bool,
     /// 配ぷよ全体
     haipuyo: Option<Vec<Kumipuyo>>,
 }
@@ -81,6 +149,8 @@ impl PlayerState {
         pending_ojama: usize,
         current_chain: usize,
         tumo_index: usize,
+        ojama_rate: usize,
+        has_zenkeshi: bool,
         haipuyo: Option<Vec<Kumipuyo>>,
     ) -> Self {
         PlayerState {
@@ -93,6 +163,8 @@ impl PlayerState {
             pending_ojama,
             current_chain,
             tumo_index,
+            ojama_rate,
+            has_zenkeshi,
             haipuyo,
         }
     }
@@ -107,6 +179,8 @@ impl PlayerState {
             pending_ojama: 0,
             current_chain: 0,
             tumo_index: 0,
+            ojama_rate: es_frame::OJAMA_PUYO_RATES[0],
+            has_zenkeshi: false,
             haipuyo,
         }
     }
@@ -121,6 +195,8 @@ impl PlayerState {
             pending_ojama: 0,
             current_chain: 0,
             tumo_index: 0,
+            ojama_rate: es_frame::OJAMA_PUYO_RATES[0],
+            has_zenkeshi: false,
             haipuyo: None,
         }
     }
@@ -149,3 +225,48 @@ impl PlayerState {
         self.field.drop_kumipuyo(decision, &self.seq[0]);
     }
 }
+
+/// 相手の状態のうち、人間が見て分かるものだけを持つ（配ぷよは持たない）
+#[derive(Clone)]
+pub struct OpponentView {
+    /// 試合開始からのフレーム数
+    pub frame: usize,
+    /// 上記フレーム数時点での盤面
+    pub field: CoreField,
+    /// 見えているツモ（ネクスト）
+    pub seq: Vec<Kumipuyo>,
+    /// 点数
+    pub score: usize,
+    /// 点数の端数・落下ボーナス・全消しボーナス の総和
+    pub carry_over: usize,
+    /// 確定おじゃまぷよ
+    pub fixed_ojama: usize,
+    /// 予告おじゃまぷよ
+    pub pending_ojama: usize,
+    /// 現在の連鎖数
+    pub current_chain: usize,
+    /// ツモ番号
+    pub tumo_index: usize,
+    /// 現在のおじゃまぷよのレート
+    pub ojama_rate: usize,
+    /// 全消しボーナスを持っているか
+    pub has_zenkeshi: bool,
+}
+
+impl From<&PlayerState> for OpponentView {
+    fn from(player_state: &PlayerState) -> Self {
+        OpponentView {
+            frame: player_state.frame,
+            field: player_state.field.clone(),
+            seq: player_state.seq.clone(),
+            score: player_state.score,
+            carry_over: player_state.carry_over,
+            fixed_ojama: player_state.fixed_ojama,
+            pending_ojama: player_state.pending_ojama,
+            current_chain: player_state.current_chain,
+            tumo_index: player_state.tumo_index,
+            ojama_rate: player_state.ojama_rate,
+            has_zenkeshi: player_state.has_zenkeshi,
+        }
+    }
+}
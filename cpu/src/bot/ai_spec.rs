@@ -0,0 +1,356 @@
+//! 文字列で指定された AI を作る
+//! - `BeamSearchAI:eval=path/to/pop_10.json,depth=30,width=60,parallel=8` のように、`名前:キー=値,...` の形で指定する
+//! - `path/to/pop_10.json` だけなら `BeamSearchAI:eval=path/to/pop_10.json` とみなす
+
+use std::{path::Path, str::FromStr, time::Duration};
+
+use puyoai::es_frame::FrameModel;
+
+use crate::{bot::*, evaluator::Evaluator};
+
+/// AI の指定
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AISpec {
+    /// AI の名前（`ai.name()`）
+    pub name: String,
+    /// (キー, 値)
+    pub options: Vec<(String, String)>,
+}
+
+impl FromStr for AISpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        if spec.ends_with(".json") && !spec.contains(':') {
+            return Ok(AISpec {
+                name: "BeamSearchAI".into(),
+                options: vec![("eval".into(), spec.into())],
+            });
+        }
+
+        let (name, options) = match spec.split_once(':') {
+            Some((name, options)) => (name, options),
+            None => (spec, ""),
+        };
+        if name.is_empty() {
+            return Err(format!("AI name is empty: {}", spec));
+        }
+
+        let options = options
+            .split(',')
+            .filter(|option| !option.trim().is_empty())
+            .map(|option| match option.split_once('=') {
+                Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+                None => Err(format!("Option must be `key=value`: {}", option)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(AISpec {
+            name: name.into(),
+            options,
+        })
+    }
+}
+
+impl AISpec {
+    /// 表示用の名前（評価関数のファイルを指定したなら、そのファイル名）
+    pub fn display_name(&self) -> String {
+        match self.get("eval") {
+            Some(path) => Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string()),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn parse_option<T: FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+        match self.get(key) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid value for `{}`: {}", key, value)),
+            None => Ok(None),
+        }
+    }
+
+    /// `keys` 以外のキーが指定されていたらエラー（打ち間違いに気づけるように）
+    pub fn expect_keys(&self, keys: &[&str]) -> Result<(), String> {
+        match self
+            .options
+            .iter()
+            .find(|(k, _)| !keys.contains(&k.as_str()))
+        {
+            Some((key, _)) => Err(format!(
+                "Unknown option for {}: `{}` (expected one of {:?})",
+                self.name, key, keys
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 指定には含まれない、AI を作るときの共通の設定
+#[derive(Clone)]
+pub struct AIContext {
+    /// フレーム数の見積もりに使うモデル
+    pub frame_model: FrameModel,
+    /// 乱数のシード（`seed=...` が指定されればそちらを優先する）
+    pub seed: Option<u64>,
+    /// `BeamSearchAI` で、同じ盤面の局面をまとめるか（`transposition=...` が指定されればそちらを優先する）
+    pub transposition: bool,
+    /// `ExternalAI` として起動するコマンド（`command=...` が指定されればそちらを優先する）
+    pub external_ai: Option<String>,
+    /// `ExternalAI` の 1 手あたりの制限時間（`timeout=...` (ms) が指定されればそちらを優先する）
+    pub external_ai_timeout: Duration,
+}
+
+impl Default for AIContext {
+    fn default() -> Self {
+        AIContext {
+            frame_model: FrameModel::default(),
+            seed: None,
+            transposition: false,
+            external_ai: None,
+            external_ai_timeout: Duration::from_millis(5000),
+        }
+    }
+}
+
+pub type AIFactory = fn(&AISpec, &AIContext) -> Result<Box<dyn AI>, String>;
+
+/// AI の名前と、それを作る関数の対応
+pub struct AIRegistry {
+    factories: Vec<(&'static str, AIFactory)>,
+}
+
+impl Default for AIRegistry {
+    /// 組み込みの AI を登録したもの
+    fn default() -> Self {
+        let mut registry = AIRegistry::new();
+        registry.register("BeamSearchAI", new_beam_search_ai);
+        registry.register("RandomAI", new_random_ai);
+        registry.register("ExternalAI", new_external_ai);
+        registry
+    }
+}
+
+impl AIRegistry {
+    /// 何も登録されていないもの
+    pub fn new() -> Self {
+        AIRegistry { factories: vec![] }
+    }
+
+    /// 同じ名前が登録されていたら置き換える
+    pub fn register(&mut self, name: &'static str, factory: AIFactory) {
+        self.factories.retain(|(n, _)| *n != name);
+        self.factories.push((name, factory));
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.factories.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn create(&self, spec: &AISpec, context: &AIContext) -> Result<Box<dyn AI>, String> {
+        match self.factories.iter().find(|(name, _)| *name == spec.name) {
+            Some((_, factory)) => factory(spec, context),
+            None => Err(format!(
+                "No AI found: {} (expected one of {:?})",
+                spec.name,
+                self.names()
+            )),
+        }
+    }
+}
+
+/// 組み込みの AI から、文字列で指定された AI を作る
+pub fn new_ai(spec: &str, context: &AIContext) -> Result<Box<dyn AI>, String> {
+    AIRegistry::default().create(&spec.parse()?, context)
+}
+
+/// - `eval`: 評価関数の JSON ファイル（`fire_thresholds` も含まれていれば、発火判断の閾値にも使う）
+/// - `depth`, `width`: 探索の深さ・幅（両方指定すると、その探索だけをする。1 以上）
+/// - `parallel`: モンテカルロの試行回数（1 以上）
+/// - `transposition`: 同じ盤面の局面をまとめるか
+/// - `seed`: 乱数のシード
+fn new_beam_search_ai(spec: &AISpec, context: &AIContext) -> Result<Box<dyn AI>, String> {
+    spec.expect_keys(&[
+        "eval",
+        "depth",
+        "width",
+        "parallel",
+        "transposition",
+        "seed",
+    ])?;
+
+    // 0 だと探索できない（思考時に panic する）ので、先に弾く
+    let positive = |key: &str| -> Result<Option<usize>, String> {
+        match spec.parse_option(key)? {
+            Some(0) => Err(format!("`{}` must be at least 1", key)),
+            value => Ok(value),
+        }
+    };
+    let search = match (positive("depth")?, positive("width")?) {
+        (Some(depth), Some(width)) => Some((depth, width)),
+        (None, None) => None,
+        _ => return Err("`depth` and `width` must be specified together".into()),
+    };
+    let parallel = positive("parallel")?;
+
+    let mut ai = match spec.get("eval") {
+        Some(path) => {
+            let json = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let evaluator: Evaluator = serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+            let ai = BeamSearchAI::new_customize(evaluator);
+
+            // GA（2人対戦）の個体なら、発火判断の閾値も含まれている
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            match value.get("fire_thresholds") {
+                Some(fire_thresholds) => {
+                    let fire_thresholds: FireThresholds =
+                        serde_json::from_value(fire_thresholds.clone())
+                            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+                    ai.with_fire_strategy(DefaultFireStrategy::new_customize(fire_thresholds))
+                }
+                None => ai,
+            }
+        }
+        None => BeamSearchAI::new(),
+    };
+
+    ai = ai
+        .with_frame_model(context.frame_model.clone())
+        .with_transposition(
+            spec.parse_option("transposition")?
+                .unwrap_or(context.transposition),
+        );
+    if let Some((depth, width)) = search {
+        ai = ai.with_search(depth, width);
+    }
+    if let Some(parallel) = parallel {
+        ai = ai.with_parallel(parallel);
+    }
+    if let Some(seed) = spec.parse_option("seed")?.or(context.seed) {
+        ai = ai.with_seed(seed);
+    }
+
+    Ok(Box::new(ai))
+}
+
+fn new_random_ai(spec: &AISpec, _context: &AIContext) -> Result<Box<dyn AI>, String> {
+    spec.expect_keys(&[])?;
+    Ok(Box::new(RandomAI::new()))
+}
+
+/// - `command`: 起動するコマンド（`,` は含められない）
+/// - `timeout`: 1 手あたりの制限時間 (ms)
+fn new_external_ai(spec: &AISpec, context: &AIContext) -> Result<Box<dyn AI>, String> {
+    spec.expect_keys(&["command", "timeout"])?;
+    let timeout = spec
+        .parse_option("timeout")?
+        .map(Duration::from_millis)
+        .unwrap_or(context.external_ai_timeout);
+    Ok(match external_ai_command(spec, context)? {
+        Some(command_line) => Box::new(ExternalAI::from_command_line(command_line, timeout)),
+        None => Box::new(ExternalAI::new()),
+    })
+}
+
+/// `ExternalAI` として起動するコマンド（`command=...` が `AIContext::external_ai` より優先される）
+fn external_ai_command<'a>(
+    spec: &'a AISpec,
+    context: &'a AIContext,
+) -> Result<Option<&'a str>, String> {
+    match spec.get("command") {
+        Some("") => Err(format!("`command` is empty for {}", spec.name)),
+        Some(command_line) => Ok(Some(command_line)),
+        None => Ok(context.external_ai.as_deref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let spec: AISpec = "BeamSearchAI:eval=path/to/pop_10.json,depth=30,width=60"
+            .parse()
+            .unwrap();
+        assert_eq!(spec.name, "BeamSearchAI");
+        assert_eq!(spec.get("eval"), Some("path/to/pop_10.json"));
+        assert_eq!(spec.parse_option::<usize>("depth"), Ok(Some(30)));
+        assert_eq!(spec.parse_option::<usize>("parallel"), Ok(None));
+        assert_eq!(spec.display_name(), "pop_10");
+
+        let spec: AISpec = "RandomAI".parse().unwrap();
+        assert_eq!(spec.name, "RandomAI");
+        assert!(spec.options.is_empty());
+
+        let spec: AISpec = "path/to/pop_10.json".parse().unwrap();
+        assert_eq!(spec.name, "BeamSearchAI");
+        assert_eq!(spec.get("eval"), Some("path/to/pop_10.json"));
+
+        assert!("BeamSearchAI:depth".parse::<AISpec>().is_err());
+        assert!(":depth=1".parse::<AISpec>().is_err());
+    }
+
+    #[test]
+    fn test_new_ai() {
+        let context = AIContext::default();
+        assert_eq!(new_ai("RandomAI", &context).unwrap().name(), "RandomAI");
+        assert!(new_ai("RandomAI:depth=1", &context).is_err());
+        assert!(new_ai("UnknownAI", &context).is_err());
+        assert!(new_ai("BeamSearchAI:depth=1", &context).is_err());
+        assert!(new_ai("BeamSearchAI:parallel=two", &context).is_err());
+        assert!(new_ai("BeamSearchAI:parallel=0", &context).is_err());
+        assert!(new_ai("BeamSearchAI:depth=0,width=60", &context).is_err());
+        assert!(new_ai("BeamSearchAI:depth=30,width=0", &context).is_err());
+    }
+
+    #[test]
+    fn test_external_ai_command() {
+        let context = AIContext {
+            external_ai: Some("./engine --fast".into()),
+            ..AIContext::default()
+        };
+
+        // `command=...` があればそちらを優先する
+        let spec: AISpec = "ExternalAI:command=./other_engine --slow,timeout=100"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            external_ai_command(&spec, &context),
+            Ok(Some("./other_engine --slow"))
+        );
+        assert_eq!(
+            new_ai("ExternalAI:command=./other_engine --slow", &context)
+                .unwrap()
+                .name(),
+            "ExternalAI"
+        );
+
+        // なければ `AIContext` のもの
+        let spec: AISpec = "ExternalAI:timeout=100".parse().unwrap();
+        assert_eq!(
+            external_ai_command(&spec, &context),
+            Ok(Some("./engine --fast"))
+        );
+        let spec: AISpec = "ExternalAI".parse().unwrap();
+        assert_eq!(external_ai_command(&spec, &AIContext::default()), Ok(None));
+
+        assert!(new_ai("ExternalAI:command=", &context).is_err());
+        assert!(new_ai("ExternalAI:cmd=./engine", &context).is_err());
+    }
+}
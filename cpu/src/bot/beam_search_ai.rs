@@ -1 +1,4 @@
 pub mod beam_search_ai;
+pub mod fire_strategy;
+pub mod worker_pool;
+pub mod zobrist;
@@ -1,24 +1,57 @@
-use std::{sync::mpsc, thread, time::Instant, vec::Vec};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, Instant},
+    vec::Vec,
+};
 
 use puyoai::{
-    color::Color,
-    column_puyo_list::ColumnPuyoList,
+    color::PuyoColor,
     decision::Decision,
-    es_field::EsCoreField,
-    field::{self, CoreField},
-    kumipuyo::{kumipuyo_seq::generate_random_puyocolor_sequence, Kumipuyo},
+    es_frame::{self, FrameModel},
+    field::CoreField,
+    kumipuyo::Kumipuyo,
     plan::Plan,
-    rensa_detector::{detector::detect_by_drop, PurposeForFindingRensa},
-    rensa_result::RensaResult,
 };
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
+use super::{
+    fire_strategy::{DefaultFireStrategy, FireContext, FireStrategy},
+    worker_pool::WorkerPool,
+    zobrist::hash_field,
+};
 use crate::{bot::*, evaluator::Evaluator, opening_matcher::OpeningMatcher};
 
+/// 時間に余裕がある限り、順に試す探索の (depth, width)
+/// TODO: チューニングする
+const SEARCH_SCHEDULE: [(usize, usize); 3] = [(20, 20), (30, 60), (40, 140)];
+
+/// モンテカルロの試行回数（スレッド数）
+const PARALLEL: usize = 20;
+
 pub struct BeamSearchAI {
     /// 盤面の評価器
     evaluator: Evaluator,
     /// 序盤のテンプレ
     opening_matcher: OpeningMatcher,
+    /// フレーム数の見積もりに使うモデル
+    frame_model: FrameModel,
+    /// 見つかった連鎖を撃つかどうかの判断
+    fire_strategy: Arc<dyn FireStrategy>,
+    /// モンテカルロで使う乱数のシード（指定しなければ、作ったときにランダムに決める）
+    seed: u64,
+    /// 同じ盤面になった局面を、深さごとに 1 つにまとめるか
+    transposition: bool,
+    /// 時間に余裕がある限り、順に試す探索の (depth, width)
+    search_schedule: Vec<(usize, usize)>,
+    /// モンテカルロの試行回数
+    parallel: usize,
+    /// モンテカルロの各試行を実行するスレッド（AI を作るだけでスレッドが立たないように、最初に思考するときに作る）
+    worker_pool: RefCell<Option<WorkerPool>>,
 }
 
 impl BeamSearchAI {
@@ -27,8 +60,79 @@ impl BeamSearchAI {
         BeamSearchAI {
             evaluator,
             opening_matcher,
+            frame_model: FrameModel::default(),
+            fire_strategy: Arc::new(DefaultFireStrategy::default()),
+            seed: rand::random(),
+            transposition: false,
+            search_schedule: SEARCH_SCHEDULE.to_vec(),
+            parallel: PARALLEL,
+            worker_pool: RefCell::new(None),
         }
     }
+
+    /// フレーム数の見積もりに使うモデルを差し替える
+    pub fn with_frame_model(mut self, frame_model: FrameModel) -> Self {
+        self.frame_model = frame_model;
+        self
+    }
+
+    /// 発火判断を差し替える
+    pub fn with_fire_strategy<S: FireStrategy + 'static>(mut self, fire_strategy: S) -> Self {
+        self.fire_strategy = Arc::new(fire_strategy);
+        self
+    }
+
+    /// 乱数のシードを固定する（探索が時間切れで打ち切られなければ、同じ局面では常に同じ手を返す）
+    /// - `think_frame` が渡されると時間いっぱい探索するので、完全に再現したいなら `with_search` で探索を固定する
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// モンテカルロで使う乱数のシード（棋譜などに残しておけば、同じ探索を再現できる）
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// 同じ盤面になった局面を、評価値が一番高いものだけ残すようにする（その分ビーム幅を活かせる）
+    pub fn with_transposition(mut self, transposition: bool) -> Self {
+        self.transposition = transposition;
+        self
+    }
+
+    /// 探索の深さ・幅を固定する（時間に余裕があっても、それ以上は読まない）
+    pub fn with_search(mut self, depth: usize, width: usize) -> Self {
+        assert!(depth > 0 && width > 0, "depth and width must be at least 1");
+        self.search_schedule = vec![(depth, width)];
+        self
+    }
+
+    /// モンテカルロの試行回数（スレッド数）を変える
+    pub fn with_parallel(mut self, parallel: usize) -> Self {
+        assert!(parallel > 0, "parallel must be at least 1");
+        self.parallel = parallel;
+        self
+    }
+
+    /// 探索の深さ・幅・試行回数を指定して思考する（テンプレは使わない。ベンチマーク用）
+    pub fn think_with_parameters(
+        &self,
+        player_state_1p: PlayerState,
+        player_state_2p: Option<OpponentView>,
+        depth: usize,
+        width: usize,
+        parallel: usize,
+    ) -> AIDecision {
+        self.think_internal(
+            player_state_1p,
+            player_state_2p,
+            depth,
+            width,
+            parallel,
+            None,
+        )
+        .unwrap()
+    }
 }
 
 impl AI for BeamSearchAI {
@@ -36,6 +140,13 @@ impl AI for BeamSearchAI {
         BeamSearchAI {
             evaluator: Evaluator::default(),
             opening_matcher: OpeningMatcher::new("opening_vis2.json").unwrap(),
+            frame_model: FrameModel::default(),
+            fire_strategy: Arc::new(DefaultFireStrategy::default()),
+            seed: rand::random(),
+            transposition: false,
+            search_schedule: SEARCH_SCHEDULE.to_vec(),
+            parallel: PARALLEL,
+            worker_pool: RefCell::new(None),
         }
     }
 
@@ -46,30 +157,8 @@ impl AI for BeamSearchAI {
     fn think(
         &self,
         player_state_1p: PlayerState,
-        player_state_2p: Option<PlayerState>,
+        player_state_2p: Option<OpponentView>,
         think_frame: Option<usize>,
-    ) -> AIDecision {
-        let think_frame = think_frame.unwrap_or(0);
-        // TODO: チューニングする
-        let (depth, width) = if think_frame <= 2 {
-            (20, 20)
-        } else if think_frame <= 8 {
-            (30, 60)
-        } else {
-            (40, 140)
-        };
-        self.think_internal(player_state_1p, player_state_2p, depth, width, 20)
-    }
-}
-
-impl BeamSearchAI {
-    fn think_internal(
-        &self,
-        player_state_1p: PlayerState,
-        player_state_2p: Option<PlayerState>,
-        depth: usize,
-        width: usize,
-        parallel: usize,
     ) -> AIDecision {
         let start = Instant::now();
 
@@ -84,193 +173,85 @@ impl BeamSearchAI {
                     &decision,
                     format!("OpeningMatcher"),
                     start.elapsed(),
-                );
+                )
+                .with_reason(DecisionReason::Opening);
             }
         }
 
-        // 相手の連鎖状況を事前に計算
-        let (rensa_result_2p, cf_after_chain_2p, estimated_rensa_results_2p) =
-            match player_state_2p.clone() {
-                Some(state) => {
-                    let mut cf = state.field.clone();
-                    let rensa_result_2p = cf.es_simulate_from_middle(state.current_chain);
-
-                    let mut estimated_rensa_results_2p: Vec<RensaResult> = vec![];
-                    let callback = |mut actual: CoreField, _cpl: &ColumnPuyoList| {
-                        estimated_rensa_results_2p.push(actual.es_simulate());
-                    };
-                    detect_by_drop(
-                        &cf,
-                        &[false; 8],
-                        PurposeForFindingRensa::ForFire,
-                        3,
-                        12,
-                        callback,
-                    );
-
-                    (
-                        Some(rensa_result_2p),
-                        Some(cf),
-                        Some(estimated_rensa_results_2p),
-                    )
-                }
-                None => (None, None, None),
-            };
-
-        // NOTE: ここで渡される state は、`State::from_plan_for_fire` から返されたもの
-        let third_row_height_1p = player_state_1p.field.height(3);
-        let fire_condition = move |state: &State, player_state_2p: &Option<PlayerState>| -> bool {
-            let plan = state.clone().plan.unwrap();
-            let ojama_from_1p_chain = (plan.score() + player_state_1p.carry_over) / 70;
-
-            // 序盤（最初の12秒）の全消し
-            if player_state_1p.frame <= 60 * 12 && plan.chain() <= 3 && plan.field().is_zenkeshi() {
-                return true;
-            }
-
-            // 凝視による発火判断
-            if let Some(player_state_2p) = player_state_2p {
-                let rensa_result_2p = rensa_result_2p.clone().unwrap();
-                let cf_after_chain_2p = cf_after_chain_2p.clone().unwrap();
-                let estimated_rensa_results_2p = estimated_rensa_results_2p.clone().unwrap();
-
-                // 2Pが発火している場合
-                if rensa_result_2p.score > 0 {
-                    // 1P 発火のための最後のツモを引くまでのフレーム数
-                    // TODO: magic number (24)
-                    let frame_1p_chain_start = player_state_1p.frame + 24 + state.frame_control;
-                    // 2P 連鎖終了までのフレーム数
-                    let frame_2p_chain_finish = player_state_2p.frame + rensa_result_2p.frame;
-                    // そもそも発火が間に合わない
-                    if frame_1p_chain_start > frame_2p_chain_finish {
-                        return false;
-                    }
-                } else {
-                    // 潰し
-                    // - 相手の地形が平ら
-                    // - 2列以上送れそう
-                    // - 3連鎖以下
-                    // - いばら
-                    let height_array_2p = player_state_2p.field.height_array();
-                    let max_height_2p = *height_array_2p[1..7].iter().max().unwrap();
-                    let min_height_2p = *height_array_2p[1..7].iter().min().unwrap();
-                    let flat = max_height_2p - min_height_2p <= 1;
-                    let score = plan.score() + player_state_1p.carry_over;
-                    let counter = estimated_rensa_results_2p
-                        .iter()
-                        .filter(|res| res.chain <= 6 && res.score >= score)
-                        .max_by(|a, b| a.score.cmp(&b.score));
-                    if flat
-                        && min_height_2p >= 2
-                        && ((plan.chain() == 1 && score >= 9 * 70)
-                            || (plan.chain() <= 3 && counter.is_none() && score >= 12 * 70))
-                    {
-                        return true;
-                    }
+        // `think_frame` が渡されたら、その時間内で探索を深く・広くしていく
+        // （時間切れで打ち切られた探索の結果は使わず、その前に読み切った結果を使う）
+        let deadline = think_frame.map(|think_frame| {
+            start + Duration::from_millis((think_frame * 1000 / es_frame::FPS) as u64)
+        });
+
+        // 最初の探索は、時間切れでも最後まで行う
+        let (depth, width) = self.search_schedule[0];
+        let mut ai_decision = self
+            .think_internal(
+                player_state_1p.clone(),
+                player_state_2p.clone(),
+                depth,
+                width,
+                self.parallel,
+                None,
+            )
+            .unwrap();
 
-                    // 埋まってたら条件をゆるく
-                    let active_puyo = {
-                        let mut cnt = 0;
-                        for x in 1..=field::WIDTH {
-                            let mut had_ojama = false;
-                            for y in (1..=player_state_2p.field.height(x)).rev() {
-                                if !player_state_2p.field.color(x, y).is_normal_color() {
-                                    if had_ojama {
-                                        break;
-                                    }
-                                    had_ojama = true;
-                                } else {
-                                    had_ojama = false;
-                                }
-                                cnt += 1;
-                            }
-                        }
-                        cnt
-                    };
-                    let buried = active_puyo <= 20 && min_height_2p >= 6;
-                    if buried
-                        && ((min_height_2p >= 9 && score >= 3 * 70)
-                            || (plan.chain() <= 3 && score >= 6 * 70))
-                    {
-                        return true;
-                    }
+        if let Some(deadline) = deadline {
+            for &(depth, width) in &self.search_schedule[1..] {
+                // 発火するなら、それ以上読まずにすぐ打つ
+                if ai_decision.is_fire() || Instant::now() >= deadline {
+                    break;
                 }
-
-                // 降る予定のおじゃまぷよ（正なら自分に、負なら相手に）
-                let ojama: isize = {
-                    let ojama_sum_1p = player_state_1p.fixed_ojama + player_state_1p.pending_ojama;
-                    let ojama_sum_2p = player_state_2p.fixed_ojama + player_state_2p.pending_ojama;
-                    let ojama_from_2p_chain = if player_state_2p.current_chain > 0 {
-                        (rensa_result_2p.score + player_state_2p.carry_over) / 70
-                    } else {
-                        0
-                    };
-
-                    (ojama_sum_1p + ojama_from_2p_chain) as isize - ojama_sum_2p as isize
-                };
-
-                // 自陣の 3 列目が埋まる可能性があるなら相殺をがんばる
-                let estimated_third_row_height = third_row_height_1p
-                    + ((ojama.max(0) as usize + field::WIDTH - 1) / field::WIDTH);
-                if ojama > 0 && estimated_third_row_height >= 12 {
-                    return ojama_from_1p_chain >= ojama as usize;
+                match self.think_internal(
+                    player_state_1p.clone(),
+                    player_state_2p.clone(),
+                    depth,
+                    width,
+                    self.parallel,
+                    Some(deadline),
+                ) {
+                    Some(deeper) => ai_decision = deeper,
+                    None => break,
                 }
+            }
+        }
 
-                // 自分の盤面に 3 つ以上降る予定の場合
-                if ojama >= 3 {
-                    let ojama = ojama as usize;
-
-                    if rensa_result_2p.chain <= 3 {
-                        // 1列以上のおじゃま
-                        if ojama > field::WIDTH {
-                            // 1段以下に軽減する
-                            return ojama_from_1p_chain + field::WIDTH >= ojama;
-                        }
-                        // 4列以上のおじゃま
-                        if ojama >= field::WIDTH * 4 {
-                            let average_height_2p: usize =
-                                cf_after_chain_2p.height_array().iter().sum::<i16>() as usize
-                                    / field::WIDTH;
-                            // 相手の連鎖発火後に 5 段以上残ってたら副砲だと判断
-                            if average_height_2p >= 5 {
-                                // 使いすぎを抑制（多くても赤玉 3 個に制限する）
-                                // TODO: 使いすぎないと返せない場合がありうる
-                                return ojama_from_1p_chain + field::WIDTH >= ojama
-                                    && ojama_from_1p_chain <= ojama + field::WIDTH * 5 * 3;
-                            }
-                        }
-                    }
+        ai_decision.elapsed = start.elapsed();
+        ai_decision
+    }
+}
 
-                    // TODO: 相手のセカンドを考慮する
-                    return ojama_from_1p_chain >= ojama;
-                }
+impl BeamSearchAI {
+    /// `deadline` までに探索が終わらなければ `None` を返す
+    fn think_internal(
+        &self,
+        player_state_1p: PlayerState,
+        player_state_2p: Option<OpponentView>,
+        depth: usize,
+        width: usize,
+        parallel: usize,
+        deadline: Option<Instant>,
+    ) -> Option<AIDecision> {
+        let start = Instant::now();
 
-                // 先打ち（8万点以上で考慮）
-                let honsen_2p = estimated_rensa_results_2p
-                    .iter()
-                    .max_by(|a, b| a.score.cmp(&b.score))
-                    .map_or(0, |res| res.score);
-                if plan.score() < 80000 {
-                    return false;
-                }
-                if plan.score() >= 80000 && honsen_2p + 30000 <= plan.score() {
-                    return true;
-                }
-                if plan.score() >= 90000 && honsen_2p + 20000 <= plan.score() {
-                    return true;
-                }
-                if plan.score() >= 100000 && honsen_2p + 10000 <= plan.score() {
-                    return true;
-                }
-                return honsen_2p <= plan.score();
-            }
+        // 相手の連鎖状況を事前に計算
+        let fire_context = Arc::new(FireContext::new(
+            player_state_1p.clone(),
+            player_state_2p,
+            &self.frame_model,
+        ));
 
-            // 飽和
-            return plan.score() >= 80000;
-        };
+        // 時間切れになったら、残りの試行を打ち切る
+        let cancel = Arc::new(AtomicBool::new(false));
+        // 発火が決まった試行の番号（の最小値）。これより番号が大きい試行は打ち切る
+        let fired = Arc::new(AtomicUsize::new(usize::MAX));
 
-        // 各スレッドの結果をまとめる
-        let (tx, rx): (mpsc::Sender<AIDecision>, mpsc::Receiver<AIDecision>) = mpsc::channel();
+        // 各スレッドの結果をまとめる（スレッドの番号つき）
+        let (tx, rx): (
+            mpsc::Sender<(usize, Option<AIDecision>)>,
+            mpsc::Receiver<(usize, Option<AIDecision>)>,
+        ) = mpsc::channel();
 
         // ツモが十分に渡されてたら、モンテカルロをする必要がない
         let parallel = if player_state_1p.seq.len() < depth {
@@ -279,200 +260,317 @@ impl BeamSearchAI {
             1
         };
 
-        for _ in 0..parallel {
+        let mut worker_pool = self.worker_pool.borrow_mut();
+        let worker_pool = worker_pool.get_or_insert_with(|| WorkerPool::new(self.parallel));
+
+        for index in 0..parallel {
             let depth_c = depth;
             let width_c = width;
             let tx_c = tx.clone();
             let player_state_1p_c = player_state_1p.clone();
-            let player_state_2p_c = player_state_2p.clone();
-            let fire_condition_c = fire_condition.clone();
+            let fire_strategy_c = self.fire_strategy.clone();
+            let fire_context_c = fire_context.clone();
             let evaluator_c = self.evaluator.clone();
-
-            thread::spawn(move || {
-                tx_c.send(think_single_thread(
-                    depth_c,
-                    width_c,
-                    &player_state_1p_c,
-                    &player_state_2p_c,
-                    fire_condition_c,
-                    &evaluator_c,
+            let frame_model_c = self.frame_model.clone();
+            let cancel_c = cancel.clone();
+            let fired_c = fired.clone();
+            let transposition = self.transposition;
+            // 探索ごと（深さ・幅ごと）・試行ごとに別の乱数にする
+            let mut rng = StdRng::seed_from_u64(derive_seed(
+                self.seed,
+                &[
+                    player_state_1p.frame as u64,
+                    player_state_1p.tumo_index as u64,
+                    depth as u64,
+                    width as u64,
+                    index as u64,
+                ],
+            ));
+
+            worker_pool.execute(move || {
+                tx_c.send((
+                    index,
+                    think_single_thread(
+                        depth_c,
+                        width_c,
+                        transposition,
+                        &player_state_1p_c,
+                        fire_strategy_c.as_ref(),
+                        &fire_context_c,
+                        &evaluator_c,
+                        &frame_model_c,
+                        &mut rng,
+                        deadline,
+                        &cancel_c,
+                        index,
+                        &fired_c,
+                    ),
                 ))
                 .ok();
             });
         }
 
-        // scores[x][r] := 解として選ばれた回数
-        let mut scores = [[0_i32; 4]; 7];
-        let mut ai_decisions = Vec::with_capacity(parallel);
-
+        let mut results: Vec<Option<AIDecision>> = vec![None; parallel];
         for _ in 0..parallel {
-            if let Ok(ai_decision) = rx.recv() {
-                // 打てるものがあったらすぐにそれを打つ（全部のスレッドでそういう結果なはずなので）
-                // TODO: 判定方法が ad-hoc すぎるのでもうちょっといい方法を考える
-                if ai_decision.log_output.contains("fire") {
-                    return AIDecision::new(
-                        ai_decision.decisions.clone(),
-                        ai_decision.log_output.clone(),
-                        start.elapsed(),
-                    );
+            match rx.recv() {
+                Ok((index, Some(ai_decision))) => {
+                    // 発火するなら、それより番号が大きい試行は打ち切る
+                    // （番号が小さい試行は最後まで待つので、どの試行の発火を使うかは終わった順に依らない）
+                    if ai_decision.is_fire() {
+                        fired.fetch_min(index, Ordering::Relaxed);
+                    }
+                    results[index] = Some(ai_decision);
                 }
-
-                let first_decision = &ai_decision.decisions[0];
-                let x = first_decision.axis_x();
-                let r = first_decision.rot();
-                scores[x][r] += 1;
-                ai_decisions.push(ai_decision);
-            } else {
-                break;
+                // 他の試行で発火が決まったので打ち切られた
+                Ok((index, None)) if index > fired.load(Ordering::Relaxed) => {}
+                // 時間切れ
+                Ok((_, None)) => {
+                    cancel.store(true, Ordering::Relaxed);
+                    return None;
+                }
+                Err(_) => break,
             }
         }
+        // スレッドが終わった順に依らないように、番号順に見る
+        let ai_decisions = results.into_iter().flatten().collect::<Vec<AIDecision>>();
+
+        // 打てるものがあったら、番号が一番小さい試行のものを打つ
+        if let Some(ai_decision) = ai_decisions
+            .iter()
+            .find(|ai_decision| ai_decision.is_fire())
+        {
+            let mut ai_decision = ai_decision.clone();
+            ai_decision.elapsed = start.elapsed();
+            return Some(ai_decision);
+        }
+
+        // scores[x][r] := 解として選ばれた回数
+        let mut scores = [[0_i32; 4]; 7];
+        for ai_decision in &ai_decisions {
+            let first_decision = &ai_decision.decisions[0];
+            scores[first_decision.axis_x()][first_decision.rot()] += 1;
+        }
 
         let best_decision = Decision::all_valid_decisions()
             .iter()
             .max_by(|d1, d2| scores[d1.axis_x()][d1.rot()].cmp(&scores[d2.axis_x()][d2.rot()]))
             .unwrap();
 
+        // 全試行の統計をまとめる
+        let statistics = SearchStatistics {
+            depth: ai_decisions
+                .iter()
+                .filter_map(|ai_decision| ai_decision.statistics.as_ref())
+                .map(|statistics| statistics.depth)
+                .min()
+                .unwrap_or(0),
+            width,
+            parallel: ai_decisions.len(),
+            nodes: ai_decisions
+                .iter()
+                .filter_map(|ai_decision| ai_decision.statistics.as_ref())
+                .map(|statistics| statistics.nodes)
+                .sum(),
+            votes: scores[best_decision.axis_x()][best_decision.rot()] as usize,
+            transpositions: ai_decisions
+                .iter()
+                .filter_map(|ai_decision| ai_decision.statistics.as_ref())
+                .map(|statistics| statistics.transpositions)
+                .sum(),
+        };
+
         if let Some(ai_decision) = ai_decisions
             .iter()
             .find(|&ai_decision| &ai_decision.decisions[0] == best_decision)
         {
-            return AIDecision::new(
-                ai_decision.decisions.clone(),
-                ai_decision.log_output.clone(),
-                start.elapsed(),
-            );
+            let mut ai_decision = ai_decision.clone().with_statistics(statistics);
+            ai_decision.elapsed = start.elapsed();
+            return Some(ai_decision);
         }
 
-        // 死ぬしかない状態でも "muri..." が入っているはずなので
+        // 死ぬしかない状態でも `DecisionReason::ForcedDeath` の手が入っているはずなので
         unreachable!()
     }
 }
 
+/// 探索中の局面（手順は持たず、親の番号だけを持つ）
 #[derive(Clone)]
-struct State {
+struct Node {
+    /// 親の `Arena` 上の番号（根なら `None`）
+    parent: Option<usize>,
+    /// 親からこの局面にするための手（根なら `None`）
+    decision: Option<Decision>,
+    /// 初手（ビーム内の初手がすべて同じかの判定用）
+    first_decision: Option<Decision>,
+    /// 何手目のツモを置いた局面か（`Plan` を作り直すときに使う）
+    depth: usize,
     field: CoreField,
-    decisions: Vec<Decision>,
     eval_score: i32,
-    plan: Option<Plan>,
     /// 発火時のツモを置くまでに必要なフレーム数
     frame_control: usize,
     /// そのツモを置くまで or 連鎖が終わり、相手にお邪魔が降るまでのフレーム数
     frame_chain: usize,
 }
 
-impl State {
-    fn empty() -> Self {
-        State {
-            field: CoreField::new(),
-            decisions: vec![],
+impl Node {
+    fn root(field: &CoreField) -> Self {
+        Node {
+            parent: None,
+            decision: None,
+            first_decision: None,
+            depth: 0,
+            field: field.clone(),
             eval_score: 0_i32,
             frame_control: 0_usize,
             frame_chain: 0_usize,
-            plan: None,
         }
     }
 
     fn from_plan(
         plan: &Plan,
-        decisions: Vec<Decision>,
+        parent: &Node,
+        parent_index: usize,
+        depth: usize,
         eval_score: i32,
-        // それまでの操作に必要なフレーム数の総和
-        frame_margin: usize,
     ) -> Self {
-        State {
+        // それまでの操作に必要なフレーム数の総和
+        let frame_margin = parent.frame_control;
+        Node {
+            parent: Some(parent_index),
+            decision: Some(plan.first_decision().clone()),
+            first_decision: parent
+                .first_decision
+                .clone()
+                .or_else(|| Some(plan.first_decision().clone())),
+            depth,
             field: plan.field().clone(),
-            decisions,
-            eval_score: eval_score,
+            eval_score,
             frame_control: frame_margin + plan.frames_to_ignite() + plan.last_drop_frames(),
             frame_chain: frame_margin + plan.total_frames(),
-            plan: Some(plan.clone()),
         }
     }
 
-    /// 発火時のState（多分別のstructに分けた方がいいけど、面倒なのでこのままで）
-    fn from_plan_for_fire(
-        plan: &Plan,
-        decisions: Vec<Decision>,
-        // 発火した連鎖の点数
-        eval_score: i32,
+    /// 発火時の局面（`eval_score` は発火した連鎖の点数）
+    fn from_plan_for_fire(plan: &Plan, parent: &Node, parent_index: usize, depth: usize) -> Self {
         // 発火時のツモ以外の操作に必要なフレーム数の総和（本来の定義とは異なることに注意）
-        frame_control: usize,
-    ) -> Self {
-        State {
-            field: plan.field().clone(),
-            decisions,
-            eval_score,
+        let frame_control = parent.frame_control;
+        Node {
             frame_control,
             frame_chain: frame_control + plan.total_frames(),
-            plan: Some(plan.clone()),
+            ..Node::from_plan(plan, parent, parent_index, depth, plan.score() as i32)
         }
     }
+}
 
-    // NOTE: 初期化にしか使っていない
-    fn from_field(field: &CoreField) -> Self {
-        State {
-            field: field.clone(),
-            decisions: vec![],
-            eval_score: 0_i32,
-            frame_control: 0_usize,
-            frame_chain: 0_usize,
-            plan: None,
+/// 1 回の探索で作った局面を持っておく
+struct Arena {
+    nodes: Vec<Node>,
+}
+
+impl Arena {
+    fn new(capacity: usize) -> Self {
+        Arena {
+            nodes: Vec::with_capacity(capacity),
         }
     }
 
-    fn first_decision(&self) -> Option<&Decision> {
-        self.decisions.first()
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn get(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    /// 根から `node` までの手順
+    fn decisions(&self, node: &Node) -> Vec<Decision> {
+        let mut decisions = vec![];
+        let mut cur = Some(node);
+        while let Some(node) = cur {
+            if let Some(decision) = &node.decision {
+                decisions.push(decision.clone());
+            }
+            cur = node.parent.map(|parent| self.get(parent));
+        }
+        decisions.reverse();
+        decisions
+    }
+
+    /// `node` の親の局面から `Plan` を作り直す（発火判断など、必要なときにだけ作る）
+    fn reconstruct_plan(&self, node: &Node, seq: &Vec<Kumipuyo>, frame_model: &FrameModel) -> Plan {
+        let parent = self.get(node.parent.unwrap());
+        let decision = node.decision.as_ref().unwrap();
+
+        let mut ret: Option<Plan> = None;
+        Plan::iterate_available_plans_with_frame_model(
+            &parent.field,
+            &vec![seq[node.depth].clone()],
+            1,
+            frame_model,
+            &mut |plan: &Plan| {
+                if ret.is_none() && plan.first_decision() == decision {
+                    ret = Some(plan.clone());
+                }
+            },
+        );
+        ret.unwrap()
     }
 }
 
 fn generate_next_states(
-    cur_state: &State,
-    next_states: &mut Vec<State>,
-    fired: &mut Vec<State>,
-    kumipuyo: &Kumipuyo,
+    arena: &Arena,
+    cur_index: usize,
+    next_states: &mut Vec<Node>,
+    fired: &mut Vec<Node>,
+    seq: &Vec<Kumipuyo>,
+    depth: usize,
     append_fired: bool,
     evaluator: &Evaluator,
+    frame_model: &FrameModel,
 ) {
-    let decisions = &cur_state.decisions;
-    let seq = vec![kumipuyo.clone()];
-
-    Plan::iterate_available_plans(&cur_state.field, &seq, 1, &mut |plan: &Plan| {
-        // TODO: どうにかできそう
-        let ds = {
-            let mut ds = decisions.clone();
-            ds.append(&mut vec![plan.first_decision().clone()]);
-            ds
-        };
+    let cur_state = arena.get(cur_index);
+
+    Plan::iterate_available_plans_with_frame_model(
+        &cur_state.field,
+        &vec![seq[depth].clone()],
+        1,
+        frame_model,
+        &mut |plan: &Plan| {
+            if append_fired && plan.chain() > 0 {
+                fired.push(Node::from_plan_for_fire(plan, cur_state, cur_index, depth));
+            }
 
-        if append_fired && plan.chain() > 0 {
-            fired.push(State::from_plan_for_fire(
+            next_states.push(Node::from_plan(
                 plan,
-                ds.clone(),
-                plan.score() as i32,
-                cur_state.frame_control,
-            ))
-        }
-
-        next_states.push(State::from_plan(
-            plan,
-            ds.clone(),
-            evaluator.evaluate(plan),
-            cur_state.frame_control,
-        ));
-    });
+                cur_state,
+                cur_index,
+                depth,
+                evaluator.evaluate(plan),
+            ));
+        },
+    );
 }
 
-fn think_single_thread<F>(
+fn think_single_thread(
     depth: usize,
     width: usize,
+    // 同じ盤面の局面をまとめるか
+    transposition: bool,
     player_state_1p: &PlayerState,
-    player_state_2p: &Option<PlayerState>,
-    fire_condition: F,
+    fire_strategy: &dyn FireStrategy,
+    fire_context: &FireContext,
     evaluator: &Evaluator,
-) -> AIDecision
-where
-    F: Fn(&State, &Option<PlayerState>) -> bool,
-{
+    frame_model: &FrameModel,
+    rng: &mut StdRng,
+    // これを過ぎたら探索を打ち切って `None` を返す
+    deadline: Option<Instant>,
+    // 他の試行が時間切れになったら立てられる（このときも `None` を返す）
+    cancel: &AtomicBool,
+    // この試行の番号（`fired` がこれより小さくなったら打ち切って `None` を返す）
+    index: usize,
+    fired: &AtomicUsize,
+) -> Option<AIDecision> {
     let start = Instant::now();
 
     let cf = &player_state_1p.field;
@@ -489,78 +587,259 @@ where
             } else {
                 0
             },
+            rng,
         ))
         .collect();
 
-    let mut state_v: Vec<State> = vec![State::from_field(cf)];
-    let mut fired_v: Vec<State> =
-        Vec::with_capacity(width * Decision::all_valid_decisions().len() * depth);
+    // ビームに残った局面だけを `arena` に入れる
+    let mut arena = Arena::new(width * depth + 1);
+    let mut state_v: Vec<usize> = vec![arena.push(Node::root(cf))];
+    let mut fired_v: Vec<Node> = Vec::with_capacity(
+        width * Decision::all_valid_decisions().len() * depth.min(visible_tumos),
+    );
+
+    // 統計用
+    let mut searched_depth = 0;
+    let mut nodes = 0;
+    let mut transpositions = 0;
 
     for depth in 0..depth {
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline)
+            || cancel.load(Ordering::Relaxed)
+            || fired.load(Ordering::Relaxed) < index
+        {
+            return None;
+        }
+
         // ビーム内の初手がすべて同じなら終わり
         if depth > 0
-            && state_v
-                .iter()
-                .all(|state| state.first_decision() == state_v[0].first_decision())
+            && state_v.iter().all(|&index| {
+                arena.get(index).first_decision == arena.get(state_v[0]).first_decision
+            })
         {
             break;
         }
 
         // 次の状態を列挙
-        let mut next_state_v: Vec<State> =
+        let mut next_state_v: Vec<Node> =
             Vec::with_capacity(width * Decision::all_valid_decisions().len());
-        for cur_state in &state_v {
+        for &cur_index in &state_v {
             generate_next_states(
-                &cur_state,
+                &arena,
+                cur_index,
                 &mut next_state_v,
                 &mut fired_v,
-                &seq[depth],
+                &seq,
+                depth,
                 depth < visible_tumos,
                 evaluator,
+                frame_model,
             );
         }
         if next_state_v.is_empty() {
             break;
         }
+        searched_depth = depth + 1;
+        nodes += next_state_v.len();
 
         // 良い方からビーム幅分だけ残す
-        next_state_v
-            .sort_by(|a: &State, b: &State| (-a.eval_score).partial_cmp(&-b.eval_score).unwrap());
-        if next_state_v.len() > width {
-            next_state_v.resize(width, State::empty());
+        next_state_v.sort_by(|a: &Node, b: &Node| b.eval_score.cmp(&a.eval_score));
+        if transposition {
+            // 同じ盤面なら、評価値が一番高い（先に来る）ものだけ残す
+            // （ハッシュが衝突しても別の盤面を落とさないように、盤面そのものも比べる）
+            let len = next_state_v.len();
+            let mut same_hash: HashMap<u64, Vec<usize>> = HashMap::with_capacity(len);
+            let mut unique_state_v: Vec<Node> = Vec::with_capacity(len);
+            for node in next_state_v {
+                let indices = same_hash.entry(hash_field(&node.field)).or_default();
+                if indices
+                    .iter()
+                    .any(|&index| unique_state_v[index].field == node.field)
+                {
+                    continue;
+                }
+                indices.push(unique_state_v.len());
+                unique_state_v.push(node);
+            }
+            transpositions += len - unique_state_v.len();
+            next_state_v = unique_state_v;
         }
-        state_v = next_state_v;
+        next_state_v.truncate(width);
+        state_v = next_state_v
+            .into_iter()
+            .map(|node| arena.push(node))
+            .collect();
     }
 
+    let statistics = SearchStatistics {
+        depth: searched_depth,
+        width,
+        parallel: 1,
+        nodes,
+        votes: 1,
+        transpositions,
+    };
+
     // 発火すべきものがあれば、その中で一番点数が高いものを選んでいる
+    // - 点数が高い順に見て、最初に発火すべきと判断したものを使う（`Plan` の復元はそこまでで済む）
+    // - 同点なら後に見つかったものを優先する（安定ソートの昇順を逆から見る）
     // TODO: 本線なら点数が最大のものでよいが、副砲ならそうではないはず？
-    if let Some(fire) = fired_v
+    fired_v.sort_by(|a: &Node, b: &Node| a.eval_score.cmp(&b.eval_score));
+    if let Some((fire, plan)) = fired_v
         .iter()
-        .filter(|f| fire_condition(f, player_state_2p))
-        .max_by(|f1, f2| f1.eval_score.cmp(&f2.eval_score))
+        .rev()
+        .map(|f| (f, arena.reconstruct_plan(f, &seq, frame_model)))
+        .find(|(f, plan)| fire_strategy.should_fire(fire_context, plan, f.frame_control))
     {
-        return AIDecision::new(
-            fire.decisions.clone(),
-            format!(
-                "fire: {:6}\n{:4} F -> {:4} F",
-                fire.eval_score, fire.frame_control, fire.frame_chain,
-            ),
-            start.elapsed(),
+        return Some(
+            AIDecision::new(
+                arena.decisions(fire),
+                format!(
+                    "fire: {:6}\n{:4} F -> {:4} F",
+                    fire.eval_score, fire.frame_control, fire.frame_chain,
+                ),
+                start.elapsed(),
+            )
+            .with_reason(DecisionReason::Fire {
+                chain: plan.chain(),
+                score: plan.score(),
+                frame_control: fire.frame_control,
+                frame_chain: fire.frame_chain,
+            })
+            .with_statistics(statistics),
         );
     }
 
-    if state_v[0].first_decision().is_some() {
-        return AIDecision::new(
-            state_v[0].decisions.clone(),
-            format!("eval: {:6}", state_v[0].eval_score),
-            start.elapsed(),
+    let best = arena.get(state_v[0]);
+    if best.first_decision.is_some() {
+        return Some(
+            AIDecision::new(
+                arena.decisions(best),
+                format!("eval: {:6}", best.eval_score),
+                start.elapsed(),
+            )
+            .with_reason(DecisionReason::Build {
+                eval_score: best.eval_score,
+            })
+            .with_statistics(statistics),
         );
     }
 
     // どうしようもないので自殺
-    return AIDecision::new(
-        vec![Decision::new(3, 0)],
-        format!("muri..."),
-        start.elapsed(),
+    return Some(
+        AIDecision::new(
+            vec![Decision::new(3, 0)],
+            format!("muri..."),
+            start.elapsed(),
+        )
+        .with_reason(DecisionReason::ForcedDeath)
+        .with_statistics(statistics),
     );
 }
+
+/// ランダムなツモ列を `rng` を使って作る
+fn generate_random_puyocolor_sequence(len: usize, rng: &mut StdRng) -> Vec<Kumipuyo> {
+    const COLORS: [PuyoColor; 4] = [
+        PuyoColor::RED,
+        PuyoColor::BLUE,
+        PuyoColor::YELLOW,
+        PuyoColor::GREEN,
+    ];
+    (0..len)
+        .map(|_| Kumipuyo::new(COLORS[rng.gen_range(0..4)], COLORS[rng.gen_range(0..4)]))
+        .collect()
+}
+
+/// シードと局面から、各スレッドで使うシードを決める（同じ局面なら同じシードになる）
+pub fn derive_seed(seed: u64, values: &[u64]) -> u64 {
+    // splitmix64
+    values.iter().fold(seed, |acc, &value| {
+        let mut z = (acc ^ value).wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_think_with_seed() {
+        let seq = vec![
+            Kumipuyo::new(PuyoColor::RED, PuyoColor::BLUE),
+            Kumipuyo::new(PuyoColor::YELLOW, PuyoColor::GREEN),
+        ];
+        let mut player_state = PlayerState::initial_state(seq, None);
+        // テンプレを使わないように
+        player_state.tumo_index = 10;
+        player_state.field = CoreField::from_str(concat!(
+            "B.....", // 3
+            "RY..G.", // 2
+            "RRYBBG"  // 1
+        ));
+
+        // 時間で打ち切られないように、探索を固定する
+        let ai = BeamSearchAI::new().with_seed(42).with_search(20, 20);
+        assert_eq!(ai.seed(), 42);
+        let ai_decision_1 = ai.think(player_state.clone(), None, Some(60));
+        let ai_decision_2 = ai.think(player_state, None, Some(60));
+        assert_eq!(ai_decision_1.decisions, ai_decision_2.decisions);
+        assert_eq!(ai_decision_1.log_output, ai_decision_2.log_output);
+        assert_eq!(ai_decision_1.statistics, ai_decision_2.statistics);
+    }
+
+    #[test]
+    fn test_think_with_transposition() {
+        let seq = vec![
+            Kumipuyo::new(PuyoColor::RED, PuyoColor::RED),
+            Kumipuyo::new(PuyoColor::BLUE, PuyoColor::BLUE),
+        ];
+        let mut player_state = PlayerState::initial_state(seq, None);
+        player_state.tumo_index = 10;
+
+        // ゾロを置く順番が違うだけの局面が出てくるので、まとめられるものがある
+        let statistics = BeamSearchAI::new()
+            .with_seed(0)
+            .with_transposition(true)
+            .think_with_parameters(player_state.clone(), None, 3, 20, 1)
+            .statistics
+            .unwrap();
+        assert!(statistics.transpositions > 0);
+
+        let statistics = BeamSearchAI::new()
+            .with_seed(0)
+            .think_with_parameters(player_state, None, 3, 20, 1)
+            .statistics
+            .unwrap();
+        assert_eq!(statistics.transpositions, 0);
+    }
+
+    #[test]
+    fn test_think_with_search() {
+        let seq = vec![
+            Kumipuyo::new(PuyoColor::RED, PuyoColor::BLUE),
+            Kumipuyo::new(PuyoColor::YELLOW, PuyoColor::GREEN),
+        ];
+        let mut player_state = PlayerState::initial_state(seq, None);
+        player_state.tumo_index = 10;
+
+        let ai = BeamSearchAI::new().with_search(3, 4).with_parallel(2);
+        // スレッドは最初に思考するときに作る
+        assert!(ai.worker_pool.borrow().is_none());
+
+        // 時間があっても、指定した探索しかしない
+        let statistics = ai.think(player_state, None, Some(60)).statistics.unwrap();
+        assert_eq!(statistics.width, 4);
+        assert_eq!(statistics.parallel, 2);
+        assert_eq!(ai.worker_pool.borrow().as_ref().unwrap().size(), 2);
+    }
+
+    #[test]
+    fn test_derive_seed() {
+        assert_eq!(derive_seed(1, &[2, 3]), derive_seed(1, &[2, 3]));
+        assert_ne!(derive_seed(1, &[2, 3]), derive_seed(1, &[3, 2]));
+        assert_ne!(derive_seed(1, &[2, 3]), derive_seed(2, &[2, 3]));
+    }
+}
@@ -0,0 +1,414 @@
+use mutate::Mutateable;
+use puyoai::{
+    color::Color,
+    column_puyo_list::ColumnPuyoList,
+    es_field::EsCoreField,
+    es_frame::{self, FrameModel},
+    field::{self, CoreField},
+    plan::Plan,
+    rensa_detector::{detector::detect_by_drop, PurposeForFindingRensa},
+    rensa_result::RensaResult,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::bot::*;
+
+/// ビームサーチ中に見つかった連鎖を撃つかどうかの判断
+pub trait FireStrategy: Send + Sync {
+    /// `plan` の連鎖を撃つべきか
+    /// - `frame_control`: 発火時のツモ以外の操作に必要なフレーム数の総和
+    fn should_fire(&self, context: &FireContext, plan: &Plan, frame_control: usize) -> bool;
+}
+
+/// 発火判断に使う局面の情報（探索の前に一度だけ計算しておく）
+pub struct FireContext {
+    pub player_state_1p: PlayerState,
+    pub opponent: Option<OpponentContext>,
+}
+
+/// 相手の連鎖状況を事前に計算したもの
+pub struct OpponentContext {
+    pub player_state_2p: OpponentView,
+    /// 相手が連鎖中なら、その連鎖の残り（連鎖中でなければ `score` が 0）
+    pub rensa_result: RensaResult,
+    /// 相手の連鎖が終わった後の盤面
+    pub cf_after_chain: CoreField,
+    /// 相手が連鎖後の盤面から撃てそうな連鎖
+    pub estimated_rensa_results: Vec<RensaResult>,
+}
+
+impl FireContext {
+    pub fn new(
+        player_state_1p: PlayerState,
+        player_state_2p: Option<OpponentView>,
+        frame_model: &FrameModel,
+    ) -> Self {
+        let opponent = player_state_2p.map(|state| {
+            let mut cf = state.field.clone();
+            let rensa_result =
+                cf.es_simulate_from_middle_with_frame_model(state.current_chain, frame_model);
+
+            let mut estimated_rensa_results: Vec<RensaResult> = vec![];
+            let callback = |mut actual: CoreField, _cpl: &ColumnPuyoList| {
+                estimated_rensa_results
+                    .push(actual.es_simulate_from_middle_with_frame_model(1, frame_model));
+            };
+            detect_by_drop(
+                &cf,
+                &[false; 8],
+                PurposeForFindingRensa::ForFire,
+                3,
+                12,
+                callback,
+            );
+
+            OpponentContext {
+                player_state_2p: state,
+                rensa_result,
+                cf_after_chain: cf,
+                estimated_rensa_results,
+            }
+        });
+
+        FireContext {
+            player_state_1p,
+            opponent,
+        }
+    }
+}
+
+/// `DefaultFireStrategy` の閾値（おじゃまぷよの数は、相手に送る個数）
+/// - 各フィールドの `#[mutate(...)]` は、GA でチューニングするときの範囲
+#[derive(Clone, Debug, Serialize, Deserialize, Mutateable)]
+#[serde(default)]
+pub struct FireThresholds {
+    // 序盤の全消し
+    #[mutate(range = 0..=60 * 30)]
+    pub zenkeshi_frame: usize,
+    #[mutate(range = 1..=5)]
+    pub zenkeshi_max_chain: usize,
+    // 相手の発火に間に合うか
+    #[mutate(range = 0..=60)]
+    pub control_margin_frame: usize,
+    // 潰し
+    #[mutate(range = 0..=6)]
+    pub crush_min_height: usize,
+    #[mutate(range = 1..=30)]
+    pub crush_single_chain_ojama: usize,
+    #[mutate(range = 1..=5)]
+    pub crush_max_chain: usize,
+    #[mutate(range = 1..=30)]
+    pub crush_ojama: usize,
+    #[mutate(range = 1..=10)]
+    pub crush_counter_max_chain: usize,
+    // 相手が埋まっているとき
+    #[mutate(range = 0..=40)]
+    pub buried_max_active_puyo: usize,
+    #[mutate(range = 0..=12)]
+    pub buried_min_height: usize,
+    #[mutate(range = 0..=12)]
+    pub buried_deep_min_height: usize,
+    #[mutate(range = 1..=30)]
+    pub buried_deep_ojama: usize,
+    #[mutate(range = 1..=5)]
+    pub buried_max_chain: usize,
+    #[mutate(range = 1..=30)]
+    pub buried_ojama: usize,
+    // 相殺
+    #[mutate(range = 6..=13)]
+    pub danger_third_row_height: usize,
+    #[mutate(range = 1..=12)]
+    pub offset_min_ojama: usize,
+    #[mutate(range = 1..=6)]
+    pub offset_max_chain_2p: usize,
+    #[mutate(range = 0..=12)]
+    pub sub_chain_min_average_height: usize,
+    #[mutate(range = 0..=180)]
+    pub offset_max_overuse_ojama: usize,
+    // 先打ち
+    #[mutate(range = 30000..=150000)]
+    pub fire_first_score_1: usize,
+    #[mutate(range = 0..=60000)]
+    pub fire_first_lead_1: usize,
+    #[mutate(range = 30000..=150000)]
+    pub fire_first_score_2: usize,
+    #[mutate(range = 0..=60000)]
+    pub fire_first_lead_2: usize,
+    #[mutate(range = 30000..=150000)]
+    pub fire_first_score_3: usize,
+    #[mutate(range = 0..=60000)]
+    pub fire_first_lead_3: usize,
+    // 飽和
+    #[mutate(range = 30000..=150000)]
+    pub saturation_score: usize,
+}
+
+impl Default for FireThresholds {
+    fn default() -> Self {
+        FireThresholds {
+            // 序盤の全消し
+            zenkeshi_frame: 60 * 12,
+            zenkeshi_max_chain: 3,
+            // 相手の発火に間に合うか
+            // TODO: magic number
+            control_margin_frame: 24,
+            // 潰し
+            crush_min_height: 2,
+            crush_single_chain_ojama: 9,
+            crush_max_chain: 3,
+            crush_ojama: 12,
+            crush_counter_max_chain: 6,
+            // 相手が埋まっているとき
+            buried_max_active_puyo: 20,
+            buried_min_height: 6,
+            buried_deep_min_height: 9,
+            buried_deep_ojama: 3,
+            buried_max_chain: 3,
+            buried_ojama: 6,
+            // 相殺
+            danger_third_row_height: 12,
+            offset_min_ojama: 3,
+            offset_max_chain_2p: 3,
+            sub_chain_min_average_height: 5,
+            // 赤玉 3 個分
+            offset_max_overuse_ojama: field::WIDTH * 5 * 3,
+            // 先打ち
+            fire_first_score_1: 80000,
+            fire_first_lead_1: 30000,
+            fire_first_score_2: 90000,
+            fire_first_lead_2: 20000,
+            fire_first_score_3: 100000,
+            fire_first_lead_3: 10000,
+            // 飽和
+            saturation_score: 80000,
+        }
+    }
+}
+
+/// 潰し・相殺・先打ちなどを閾値で判断する
+#[derive(Clone, Debug, Default)]
+pub struct DefaultFireStrategy {
+    pub thresholds: FireThresholds,
+}
+
+impl DefaultFireStrategy {
+    pub fn new_customize(thresholds: FireThresholds) -> Self {
+        DefaultFireStrategy { thresholds }
+    }
+}
+
+impl FireStrategy for DefaultFireStrategy {
+    fn should_fire(&self, context: &FireContext, plan: &Plan, frame_control: usize) -> bool {
+        let th = &self.thresholds;
+        let player_state_1p = &context.player_state_1p;
+
+        let ojama_rate = player_state_1p.ojama_rate;
+        // 全消しボーナスを持っていれば、この連鎖で一緒に送られる
+        let ojama_from_1p_chain = (plan.score() + player_state_1p.carry_over) / ojama_rate
+            + if player_state_1p.has_zenkeshi {
+                es_frame::ZENKESHI_OJAMA
+            } else {
+                0
+            };
+
+        // 序盤の全消し
+        if player_state_1p.frame <= th.zenkeshi_frame
+            && plan.chain() <= th.zenkeshi_max_chain
+            && plan.has_zenkeshi()
+        {
+            return true;
+        }
+
+        // 凝視による発火判断
+        if let Some(opponent) = &context.opponent {
+            let player_state_2p = &opponent.player_state_2p;
+            let rensa_result_2p = &opponent.rensa_result;
+
+            // 2Pが発火している場合
+            if rensa_result_2p.score > 0 {
+                // 1P 発火のための最後のツモを引くまでのフレーム数
+                let frame_1p_chain_start =
+                    player_state_1p.frame + th.control_margin_frame + frame_control;
+                // 2P 連鎖終了までのフレーム数
+                let frame_2p_chain_finish = player_state_2p.frame + rensa_result_2p.frame;
+                // そもそも発火が間に合わない
+                if frame_1p_chain_start > frame_2p_chain_finish {
+                    return false;
+                }
+            } else {
+                // 潰し
+                // - 相手の地形が平ら
+                // - 2列以上送れそう
+                // - 3連鎖以下
+                // - いばら
+                let height_array_2p = player_state_2p.field.height_array();
+                let max_height_2p = *height_array_2p[1..7].iter().max().unwrap() as usize;
+                let min_height_2p = *height_array_2p[1..7].iter().min().unwrap() as usize;
+                let flat = max_height_2p - min_height_2p <= 1;
+                let score = plan.score() + carry_over_1p;
+                let counter = opponent
+                    .estimated_rensa_results
+                    .iter()
+                    .filter(|res| res.chain <= th.crush_counter_max_chain && res.score >= score)
+                    .max_by(|a, b| a.score.cmp(&b.score));
+                if flat
+                    && min_height_2p >= th.crush_min_height
+                    && ((plan.chain() == 1 && score >= th.crush_single_chain_ojama * ojama_rate)
+                        || (plan.chain() <= th.crush_max_chain
+                            && counter.is_none()
+                            && score >= th.crush_ojama * ojama_rate))
+                {
+                    return true;
+                }
+
+                // 埋まってたら条件をゆるく
+                let active_puyo = {
+                    let mut cnt = 0;
+                    for x in 1..=field::WIDTH {
+                        let mut had_ojama = false;
+                        for y in (1..=player_state_2p.field.height(x)).rev() {
+                            if !player_state_2p.field.color(x, y).is_normal_color() {
+                                if had_ojama {
+                                    break;
+                                }
+                                had_ojama = true;
+                            } else {
+                                had_ojama = false;
+                            }
+                            cnt += 1;
+                        }
+                    }
+                    cnt
+                };
+                let buried = active_puyo <= th.buried_max_active_puyo
+                    && min_height_2p >= th.buried_min_height;
+                if buried
+                    && ((min_height_2p >= th.buried_deep_min_height
+                        && score >= th.buried_deep_ojama * ojama_rate)
+                        || (plan.chain() <= th.buried_max_chain
+                            && score >= th.buried_ojama * ojama_rate))
+                {
+                    return true;
+                }
+            }
+
+            // 降る予定のおじゃまぷよ（正なら自分に、負なら相手に）
+            let ojama: isize = {
+                let ojama_sum_1p = player_state_1p.fixed_ojama + player_state_1p.pending_ojama;
+                let ojama_sum_2p = player_state_2p.fixed_ojama + player_state_2p.pending_ojama;
+                let ojama_from_2p_chain = if player_state_2p.current_chain > 0 {
+                    (rensa_result_2p.score + player_state_2p.carry_over) / ojama_rate
+                } else {
+                    0
+                };
+
+                (ojama_sum_1p + ojama_from_2p_chain) as isize - ojama_sum_2p as isize
+            };
+
+            // 自陣の 3 列目が埋まる可能性があるなら相殺をがんばる
+            let estimated_third_row_height = player_state_1p.field.height(3)
+                + ((ojama.max(0) as usize + field::WIDTH - 1) / field::WIDTH);
+            if ojama > 0 && estimated_third_row_height >= th.danger_third_row_height {
+                return ojama_from_1p_chain >= ojama as usize;
+            }
+
+            // 自分の盤面にある程度降る予定の場合
+            if ojama >= th.offset_min_ojama as isize {
+                let ojama = ojama as usize;
+
+                if rensa_result_2p.chain <= th.offset_max_chain_2p {
+                    // 1列以上のおじゃま
+                    if ojama > field::WIDTH {
+                        // 1段以下に軽減する
+                        return ojama_from_1p_chain + field::WIDTH >= ojama;
+                    }
+                    // 4列以上のおじゃま
+                    if ojama >= field::WIDTH * 4 {
+                        let average_height_2p: usize =
+                            opponent.cf_after_chain.height_array().iter().sum::<i16>() as usize
+                                / field::WIDTH;
+                        // 相手の連鎖発火後にある程度残ってたら副砲だと判断
+                        if average_height_2p >= th.sub_chain_min_average_height {
+                            // 使いすぎを抑制する
+                            // TODO: 使いすぎないと返せない場合がありうる
+                            return ojama_from_1p_chain + field::WIDTH >= ojama
+                                && ojama_from_1p_chain <= ojama + th.offset_max_overuse_ojama;
+                        }
+                    }
+                }
+
+                // TODO: 相手のセカンドを考慮する
+                return ojama_from_1p_chain >= ojama;
+            }
+
+            // 先打ち
+            let honsen_2p = opponent
+                .estimated_rensa_results
+                .iter()
+                .max_by(|a, b| a.score.cmp(&b.score))
+                .map_or(0, |res| res.score);
+            if plan.score() < th.fire_first_score_1 {
+                return false;
+            }
+            for &(score, lead) in &[
+                (th.fire_first_score_1, th.fire_first_lead_1),
+                (th.fire_first_score_2, th.fire_first_lead_2),
+                (th.fire_first_score_3, th.fire_first_lead_3),
+            ] {
+                if plan.score() >= score && honsen_2p + lead <= plan.score() {
+                    return true;
+                }
+            }
+            return honsen_2p <= plan.score();
+        }
+
+        // 飽和
+        return plan.score() >= th.saturation_score;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use puyoai::{color::PuyoColor, kumipuyo::Kumipuyo};
+
+    use super::*;
+
+    fn zenkeshi_plan() -> Plan {
+        let cf = CoreField::from_str("RR....");
+        let seq = vec![Kumipuyo::new(PuyoColor::RED, PuyoColor::RED)];
+        let mut plans = vec![];
+        Plan::iterate_available_plans(&cf, &seq, 1, &mut |plan: &Plan| {
+            if plan.has_zenkeshi() {
+                plans.push(plan.clone());
+            }
+        });
+        plans.pop().unwrap()
+    }
+
+    #[test]
+    fn test_early_zenkeshi() {
+        let strategy = DefaultFireStrategy::default();
+        let plan = zenkeshi_plan();
+
+        let mut player_state_1p = PlayerState::initial_state(vec![], None);
+        player_state_1p.field = CoreField::from_str("RR....");
+        let context = FireContext::new(player_state_1p.clone(), None, &FrameModel::default());
+        assert!(strategy.should_fire(&context, &plan, 0));
+
+        // 序盤でなければ、飽和するまで撃たない
+        player_state_1p.frame = 60 * 13;
+        let context = FireContext::new(player_state_1p, None, &FrameModel::default());
+        assert!(!strategy.should_fire(&context, &plan, 0));
+    }
+
+    #[test]
+    fn test_deserialize_fire_thresholds() {
+        let thresholds: FireThresholds =
+            serde_json::from_str(r#"{ "saturation_score": 50000 }"#).unwrap();
+        assert_eq!(thresholds.saturation_score, 50000);
+        assert_eq!(
+            thresholds.fire_first_score_1,
+            FireThresholds::default().fire_first_score_1
+        );
+    }
+}
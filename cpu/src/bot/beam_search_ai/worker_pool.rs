@@ -0,0 +1,106 @@
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// 使い回すスレッドの集まり（思考のたびにスレッドを作らないように）
+pub struct WorkerPool {
+    /// `None` になったら各スレッドが終了する
+    sender: Mutex<Option<mpsc::Sender<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..size)
+            .map(|_| {
+                let receiver = receiver.clone();
+                thread::spawn(move || loop {
+                    // ロックは受け取るまでの間だけ持つ
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+
+        WorkerPool {
+            sender: Mutex::new(Some(sender)),
+            workers,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// 空いているスレッドで `job` を実行する
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            sender.send(Box::new(job)).ok();
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.sender.lock().unwrap().take();
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let pool = WorkerPool::new(4);
+        assert_eq!(pool.size(), 4);
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..10 {
+            let count = count.clone();
+            let tx = tx.clone();
+            pool.execute(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+                tx.send(()).ok();
+            });
+        }
+        for _ in 0..10 {
+            rx.recv().unwrap();
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn test_drop_waits_for_jobs() {
+        let count = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = WorkerPool::new(2);
+            for _ in 0..5 {
+                let count = count.clone();
+                pool.execute(move || {
+                    count.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        }
+        // 送ったものは全部実行されてから終了する
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+    }
+}
@@ -0,0 +1,93 @@
+use puyoai::{
+    color::PuyoColor,
+    field::{self, CoreField},
+};
+
+/// 色の種類（赤・青・黄・緑・おじゃま・その他）
+const COLOR_KINDS: usize = 6;
+/// 14 段目まで（+ 番兵）
+const ROWS: usize = 16;
+
+/// ZOBRIST_TABLE[x][y][color] := その位置にその色があるときに xor する値
+const ZOBRIST_TABLE: [[[u64; COLOR_KINDS]; ROWS]; field::WIDTH + 1] = generate_table();
+
+const fn generate_table() -> [[[u64; COLOR_KINDS]; ROWS]; field::WIDTH + 1] {
+    let mut table = [[[0; COLOR_KINDS]; ROWS]; field::WIDTH + 1];
+    // splitmix64（固定のシードから作るので、毎回同じ表になる）
+    let mut state: u64 = 0x6768_6F74_6968_6173;
+    let mut x = 0;
+    while x <= field::WIDTH {
+        let mut y = 0;
+        while y < ROWS {
+            let mut c = 0;
+            while c < COLOR_KINDS {
+                state = state.wrapping_add(0x9E3779B97F4A7C15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+                table[x][y][c] = z ^ (z >> 31);
+                c += 1;
+            }
+            y += 1;
+        }
+        x += 1;
+    }
+    table
+}
+
+fn color_index(color: PuyoColor) -> usize {
+    match color {
+        PuyoColor::RED => 0,
+        PuyoColor::BLUE => 1,
+        PuyoColor::YELLOW => 2,
+        PuyoColor::GREEN => 3,
+        PuyoColor::OJAMA => 4,
+        _ => 5,
+    }
+}
+
+/// 盤面の Zobrist hash（おじゃまぷよも含めて、置かれているぷよが同じなら同じ値）
+pub fn hash_field(cf: &CoreField) -> u64 {
+    let mut hash = 0;
+    for x in 1..=field::WIDTH {
+        for y in 1..=cf.height(x).min(ROWS - 1) {
+            hash ^= ZOBRIST_TABLE[x][y][color_index(cf.color(x, y))];
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_field() {
+        let cf = CoreField::from_str(concat!(
+            "O.....", // 2
+            "RRYBBG"  // 1
+        ));
+        assert_eq!(hash_field(&cf), hash_field(&cf.clone()));
+        assert_eq!(hash_field(&CoreField::new()), 0);
+
+        // 色・位置・おじゃまが違えば別の値
+        let others = [
+            CoreField::from_str(concat!(
+                "O.....", // 2
+                "RRYBBY"  // 1
+            )),
+            CoreField::from_str(concat!(
+                ".O....", // 2
+                "RRYBBG"  // 1
+            )),
+            CoreField::from_str(concat!(
+                "R.....", // 2
+                "RRYBBG"  // 1
+            )),
+            CoreField::from_str("RRYBBG"),
+        ];
+        for other in &others {
+            assert_ne!(hash_field(&cf), hash_field(other));
+        }
+    }
+}
@@ -0,0 +1 @@
+pub mod external_ai;
@@ -0,0 +1,268 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use puyoai::{control::PuyoController, decision::Decision, field::CoreField};
+
+use crate::{
+    bot::*,
+    upi::pfen::{format_pfen_field, format_pfen_tumos, parse_move},
+};
+
+/// 外部プロセスとして起動した AI と UPI で通信する
+pub struct ExternalAI {
+    /// 起動するコマンド
+    program: String,
+    /// コマンドライン引数
+    args: Vec<String>,
+    /// 1手あたりの制限時間（起動時の `upi` / `isready` にも使う）
+    timeout: Duration,
+    /// 起動中のプロセス（落ちた・時間切れになったら捨てて、次の思考時に起動し直す）
+    process: Mutex<Option<EngineProcess>>,
+}
+
+impl ExternalAI {
+    pub fn new_customize(program: &str, args: Vec<String>, timeout: Duration) -> Self {
+        ExternalAI {
+            program: program.into(),
+            args,
+            timeout,
+            process: Mutex::new(None),
+        }
+    }
+
+    /// `"path/to/engine --option value"` のような文字列から作る
+    pub fn from_command_line(command_line: &str, timeout: Duration) -> Self {
+        let mut tokens = command_line.split_whitespace().map(String::from);
+        let program = tokens.next().unwrap_or_default();
+        ExternalAI::new_customize(&program, tokens.collect(), timeout)
+    }
+
+    fn think_external(
+        &self,
+        process: &mut Option<EngineProcess>,
+        player_state_1p: &PlayerState,
+        player_state_2p: &Option<OpponentView>,
+    ) -> Result<(Decision, String), String> {
+        if process.is_none() {
+            *process = Some(EngineProcess::spawn(
+                &self.program,
+                &self.args,
+                self.timeout,
+            )?);
+        }
+        let process = process.as_mut().unwrap();
+
+        let mut position = format!(
+            "position pfen {}",
+            format_position(&OpponentView::from(player_state_1p))
+        );
+        if let Some(player_state_2p) = player_state_2p {
+            position.push(' ');
+            position.push_str(&format_position(player_state_2p));
+        }
+        process.send(&position)?;
+        process.send("go")?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut info = String::new();
+        loop {
+            let line = process.receive(deadline)?;
+            if let Some(rest) = line.strip_prefix("info ") {
+                if let Some((_, s)) = rest.split_once("string ") {
+                    info = s.to_string();
+                }
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix("bestmove ") {
+                let decision = parse_move(rest.trim())?;
+                if !PuyoController::new().is_reachable(&player_state_1p.field, &decision) {
+                    return Err(format!("unreachable move: {}", rest.trim()));
+                }
+                return Ok((decision, info));
+            }
+        }
+    }
+}
+
+impl AI for ExternalAI {
+    /// `upi_engine` を起動する（実行中のバイナリと同じディレクトリにあるものを使う）
+    fn new() -> Self {
+        let program = std::env::current_exe()
+            .map(|path| {
+                path.with_file_name(format!("upi_engine{}", std::env::consts::EXE_SUFFIX))
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .unwrap_or(format!("upi_engine{}", std::env::consts::EXE_SUFFIX));
+        ExternalAI::new_customize(&program, vec![], Duration::from_secs(5))
+    }
+
+    fn name(&self) -> &'static str {
+        "ExternalAI"
+    }
+
+    fn think(
+        &self,
+        player_state_1p: PlayerState,
+        player_state_2p: Option<OpponentView>,
+        _think_frame: Option<usize>,
+    ) -> AIDecision {
+        let start = Instant::now();
+
+        let mut process = self.process.lock().unwrap();
+        match self.think_external(&mut process, &player_state_1p, &player_state_2p) {
+            Ok((decision, info)) => {
+                AIDecision::from_decision(&decision, format!("external: {}", info), start.elapsed())
+                    .with_reason(DecisionReason::External)
+            }
+            Err(e) => {
+                // 次の思考時に起動し直す
+                *process = None;
+                AIDecision::from_decision(
+                    &fallback_decision(&player_state_1p.field),
+                    format!("external error: {}", e),
+                    start.elapsed(),
+                )
+                .with_reason(DecisionReason::Fallback)
+            }
+        }
+    }
+}
+
+/// 1人分の局面を `<盤面> <ツモ> <おじゃま>` の形式にする
+fn format_position(player_state: &OpponentView) -> String {
+    format!(
+        "{} {} {}",
+        format_pfen_field(&player_state.field),
+        format_pfen_tumos(&player_state.seq),
+        player_state.fixed_ojama + player_state.pending_ojama
+    )
+}
+
+/// 外部 AI が答えられなかったときに置く場所（なるべく 3 列目に置く）
+fn fallback_decision(field: &CoreField) -> Decision {
+    let controller = PuyoController::new();
+    std::iter::once(&Decision::new(3, 0))
+        .chain(Decision::all_valid_decisions().iter())
+        .find(|decision| controller.is_reachable(field, decision))
+        .unwrap_or(&Decision::new(3, 0))
+        .clone()
+}
+
+struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    /// 標準出力を 1 行ずつ受け取る（タイムアウトさせるために別スレッドで読む）
+    lines: mpsc::Receiver<String>,
+}
+
+impl EngineProcess {
+    fn spawn(program: &str, args: &Vec<String>, timeout: Duration) -> Result<Self, String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to spawn {}: {}", program, e))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut process = EngineProcess {
+            child,
+            stdin,
+            lines: rx,
+        };
+
+        let deadline = Instant::now() + timeout;
+        process.send("upi")?;
+        while process.receive(deadline)? != "upiok" {}
+        process.send("isready")?;
+        while process.receive(deadline)? != "readyok" {}
+
+        Ok(process)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("failed to send: {}", e))
+    }
+
+    fn receive(&mut self, deadline: Instant) -> Result<String, String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line.trim().to_string()),
+            Err(RecvTimeoutError::Timeout) => Err("timeout".into()),
+            Err(RecvTimeoutError::Disconnected) => Err("process exited".into()),
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        self.send("quit").ok();
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use puyoai::{color::PuyoColor, kumipuyo::Kumipuyo};
+
+    use super::*;
+
+    #[test]
+    fn test_format_position() {
+        let mut player_state = PlayerState::initial_state(
+            vec![
+                Kumipuyo::new(PuyoColor::RED, PuyoColor::BLUE),
+                Kumipuyo::new(PuyoColor::YELLOW, PuyoColor::GREEN),
+            ],
+            None,
+        );
+        player_state.field = CoreField::from_str("RR..GO");
+        player_state.fixed_ojama = 3;
+        player_state.pending_ojama = 4;
+
+        assert_eq!(
+            format_position(&OpponentView::from(&player_state)),
+            "r/r///g/o/ rb/yg/ 7"
+        );
+    }
+
+    #[test]
+    fn test_spawn_failure() {
+        let ai =
+            ExternalAI::new_customize("ghoti-no-such-engine", vec![], Duration::from_millis(100));
+        let player_state =
+            PlayerState::initial_state(vec![Kumipuyo::new(PuyoColor::RED, PuyoColor::BLUE)], None);
+
+        let ai_decision = ai.think(player_state, None, None);
+        assert_eq!(ai_decision.decisions[0], Decision::new(3, 0));
+        assert!(ai_decision.log_output.starts_with("external error"));
+        assert_eq!(ai_decision.reason, DecisionReason::Fallback);
+    }
+}
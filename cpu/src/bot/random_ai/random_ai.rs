@@ -19,7 +19,7 @@ impl AI for RandomAI {
     fn think(
         &self,
         player_state_1p: PlayerState,
-        _player_state_2p: Option<PlayerState>,
+        _player_state_2p: Option<OpponentView>,
         _think_frame: Option<usize>,
     ) -> AIDecision {
         let start = Instant::now();
@@ -35,7 +35,8 @@ impl AI for RandomAI {
                     decision,
                     format!("Random (retry: {retry})"),
                     start.elapsed(),
-                );
+                )
+                .with_reason(DecisionReason::Random);
             }
             retry += 1;
         }
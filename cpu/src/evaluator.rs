@@ -1,4 +1,6 @@
 pub mod detect_shape;
 pub mod evaluator;
+pub mod feature_vector;
 
-pub use evaluator::Evaluator;
+pub use evaluator::{EvaluationTerm, Evaluator, Explanation};
+pub use feature_vector::{extract_features, FeatureVector};
@@ -1,91 +1,189 @@
-use puyoai::{
-    color::Color,
-    column_puyo_list::ColumnPuyoList,
-    field::{self, CoreField},
-    plan::Plan,
-    rensa_detector::{detector::detect_by_drop, PurposeForFindingRensa},
-    rensa_result::RensaResult,
-};
+use mutate::Mutateable;
+use puyoai::{field, plan::Plan};
 use serde::{Deserialize, Serialize};
 
-use super::detect_shape::*;
+use super::feature_vector::{extract_features, FeatureVector};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// `top_row` の各列の項の名前
+const TOP_ROW_NAMES: [&str; field::WIDTH] = [
+    "top_row_1",
+    "top_row_2",
+    "top_row_3",
+    "top_row_4",
+    "top_row_5",
+    "top_row_6",
+];
+
+/// 評価値の 1 項分の内訳
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EvaluationTerm {
+    /// 項の名前
+    pub name: &'static str,
+    /// 重みとして使っているパラメータの名前（`top_row` は `top_row_1` のように列ごと）
+    pub parameter: &'static str,
+    /// 特徴量の値
+    pub value: f32,
+    /// 重み
+    pub weight: i32,
+    /// 評価値への寄与
+    pub contribution: i32,
+}
+
+/// 評価値とその内訳
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Explanation {
+    pub score: i32,
+    /// 死んでいる盤面なら空
+    pub terms: Vec<EvaluationTerm>,
+}
+
+/// 各フィールドの `#[mutate(...)]` は、GA で初期個体を作るときの範囲
+/// - 交叉では、全てのフィールドを `-999..=999` に収め、±10 だけずらす（チューニングで取りうる範囲もこれ）
+#[derive(Clone, Debug, Serialize, Deserialize, Mutateable)]
+#[mutate(crossover(range = -999..1000, noise = 10))]
 pub struct Evaluator {
     // 盤面
+    #[mutate(range = -999..0)]
     pub valley: i32,
+    #[mutate(range = -999..0)]
     pub ridge: i32,
+    #[mutate(range = -999..1000)]
     pub ideal_height_diff: i32,
+    #[mutate(range = -999..1000)]
     pub ideal_height_diff_sq: i32,
+    #[mutate(range = -999..1000)]
     pub ideal_height_coef_1: i32,
+    #[mutate(range = -999..1000)]
     pub ideal_height_coef_2: i32,
+    #[mutate(range = -999..1000)]
     pub ideal_height_coef_3: i32,
+    #[mutate(range = -999..1000)]
     pub ideal_height_coef_4: i32,
+    #[mutate(range = -999..1000)]
     pub third_column_height: i32,
+    #[mutate(range = -999..1000)]
     pub third_column_height_sq: i32,
+    #[mutate(range = -999..0)]
     pub unreachable_space: i32,
+    #[mutate(ranges = [-999..1000, -999..0, -999..-990, -999..0, -999..0, -999..1000])]
     pub top_row: [i32; field::WIDTH],
     // 連結
+    #[mutate(range = 0..1000)]
     pub connectivity_2: i32,
+    #[mutate(range = 0..1000)]
     pub connectivity_3: i32,
     // 発生した連鎖
+    #[mutate(range = -999..1000)]
     pub chain: i32,
+    #[mutate(range = -999..1000)]
     pub chain_sq: i32,
+    #[mutate(range = -999..1000)]
     pub chain_score: i32,
+    #[mutate(range = -999..0)]
     pub chain_frame: i32,
     // 盤面から起こりうる連鎖
+    #[mutate(range = -999..1000)]
     pub potential_main_chain: i32,
+    #[mutate(range = -999..1000)]
     pub potential_main_chain_sq: i32,
+    #[mutate(range = -999..0)]
     pub potential_main_chain_frame: i32,
+    #[mutate(range = 0..1000)]
     pub potential_main_chain_ignition_height: i32,
+    #[mutate(range = -999..1000)]
     pub potential_sub_chain: i32,
+    #[mutate(range = -999..1000)]
     pub potential_sub_chain_sq: i32,
+    #[mutate(range = -999..0)]
     pub potential_sub_chain_frame: i32,
+    #[mutate(range = 0..1000)]
     pub potential_sub_chain_ignition_height: i32,
     // フレーム関係
+    #[mutate(range = -999..0)]
     pub chigiri: i32,
+    #[mutate(range = -999..0)]
     pub move_frame: i32,
     // パターンマッチング
+    #[mutate(range = 0..1000)]
     pub gtr_base_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_base_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_base_3: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_base_4: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_base_5: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_base_6: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_base_7: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_3: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_4: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_5: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_6: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_1_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_1_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_1_3: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_2_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_2_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_2_3: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_2_4: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_2_5: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_2_6: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_2_7: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_3_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_3_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_3_3: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_3_4: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_4_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_5_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_5_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_6_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_6_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_tail_6_3: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_head_1: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_head_2: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_head_3: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_head_4: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_head_5: i32,
+    #[mutate(range = 0..1000)]
     pub gtr_head_6: i32,
     // チューニング用
+    #[mutate(sub_name)]
     pub sub_name: Option<String>,
 }
 
@@ -196,37 +294,69 @@ impl Evaluator {
     }
 
     pub fn evaluate(&self, plan: &Plan) -> i32 {
-        let cf = plan.field();
-        let res = plan.rensa_result();
+        self.evaluate_features(&extract_features(plan))
+    }
 
-        if cf.is_dead() {
+    /// `extract_features` で計算した特徴量から評価値を計算する
+    pub fn evaluate_features(&self, features: &FeatureVector) -> i32 {
+        self.evaluate_internal(features, |_| {})
+    }
+
+    /// 評価値を、項ごとの特徴量の値・重み・寄与に分けて返す（デバッグ用）
+    pub fn explain(&self, plan: &Plan) -> Explanation {
+        self.explain_features(&extract_features(plan))
+    }
+
+    /// `extract_features` で計算した特徴量から、評価値の内訳を計算する
+    pub fn explain_features(&self, features: &FeatureVector) -> Explanation {
+        let mut terms = vec![];
+        let score = self.evaluate_internal(features, |term| terms.push(term));
+        Explanation { score, terms }
+    }
+
+    /// 評価値を計算する。各項は `record` にも渡す
+    fn evaluate_internal<F>(&self, features: &FeatureVector, mut record: F) -> i32
+    where
+        F: FnMut(EvaluationTerm),
+    {
+        if features.is_dead {
             return i32::MIN >> 7;
         }
 
         let mut score = 0_i32;
 
+        // `term!(名前, 重み, 値)` で `重み * 値` を足す（寄与を別に計算する場合は 4 つ目に渡す）
+        // 項の名前とパラメータの名前が違う場合は `名前 => パラメータの名前` と書く
+        macro_rules! term {
+            (@record $name:expr, $parameter:expr, $weight:expr, $value:expr, $contribution:expr) => {{
+                let contribution: i32 = $contribution;
+                score += contribution;
+                record(EvaluationTerm {
+                    name: $name,
+                    parameter: $parameter,
+                    value: $value,
+                    weight: $weight,
+                    contribution,
+                });
+            }};
+            ($name:expr => $parameter:expr, $weight:expr, $value:expr) => {{
+                let value: i32 = $value;
+                term!(@record $name, $parameter, $weight, value as f32, $weight * value);
+            }};
+            ($name:expr, $weight:expr, $value:expr) => {
+                term!($name => $name, $weight, $value)
+            };
+            ($name:expr, $weight:expr, $value:expr, $contribution:expr) => {
+                term!(@record $name, $name, $weight, $value, $contribution)
+            };
+        }
+
         {
             // 盤面
-            for x in 1..=field::WIDTH {
-                score += self.valley * cf.valley_depth(x) as i32;
-                score += self.ridge * cf.ridge_height(x) as i32;
-            }
+            term!("valley", self.valley, features.valley);
+            term!("ridge", self.ridge, features.ridge);
 
-            let average_height = average_height(cf);
-            let mut diff_sum = 0.0;
-            let mut diff_sq_sum = 0.0;
-            for x in 1..=field::WIDTH {
-                let ideal_height = average_height
-                    + match x {
-                        1 | 6 => 2.0,
-                        3 | 4 => -2.0,
-                        _ => 0.0,
-                    };
-
-                let diff = ideal_height - cf.height(x) as f32;
-                diff_sum += diff.abs();
-                diff_sq_sum += diff * diff;
-            }
+            let average_height = features.average_height;
             let coef = if average_height < 1.0 {
                 0.0
             } else if average_height < 3.0 {
@@ -241,68 +371,118 @@ impl Evaluator {
                 1.0
             };
 
-            score += (self.ideal_height_diff as f32 * diff_sum * coef) as i32;
-            score += (self.ideal_height_diff_sq as f32 * diff_sq_sum * coef) as i32;
+            let diff_sum = features.ideal_height_diff;
+            let diff_sq_sum = features.ideal_height_diff_sq;
+            term!(
+                "ideal_height_diff",
+                self.ideal_height_diff,
+                diff_sum * coef,
+                (self.ideal_height_diff as f32 * diff_sum * coef) as i32
+            );
+            term!(
+                "ideal_height_diff_sq",
+                self.ideal_height_diff_sq,
+                diff_sq_sum * coef,
+                (self.ideal_height_diff_sq as f32 * diff_sq_sum * coef) as i32
+            );
 
-            score += self.third_column_height * cf.height(3) as i32;
-            score += self.third_column_height_sq * (cf.height(3) * cf.height(3)) as i32;
+            let height = features.third_column_height;
+            term!("third_column_height", self.third_column_height, height);
+            term!(
+                "third_column_height_sq",
+                self.third_column_height_sq,
+                height * height
+            );
 
-            score += self.unreachable_space * cf.count_unreachable_spaces() as i32;
+            term!(
+                "unreachable_space",
+                self.unreachable_space,
+                features.unreachable_space
+            );
 
             for x in 1..=field::WIDTH {
-                if !cf.is_empty(x, 13) {
-                    score += self.top_row[x - 1];
-                }
+                term!(
+                    TOP_ROW_NAMES[x - 1],
+                    self.top_row[x - 1],
+                    features.top_row[x - 1] as i32
+                );
             }
         }
 
         {
             // 連結
-            let connectivity = connectivity(cf);
-            score += self.connectivity_2 * connectivity[2];
-            score += self.connectivity_3 * connectivity[3];
+            term!(
+                "connectivity_2",
+                self.connectivity_2,
+                features.connectivity_2
+            );
+            term!(
+                "connectivity_3",
+                self.connectivity_3,
+                features.connectivity_3
+            );
         }
 
         {
             // 発生した連鎖
-            score += self.chain * res.chain as i32;
-            score += self.chain_sq * (res.chain * res.chain) as i32;
-            score += self.chain_score * (res.score / 1000) as i32;
-            score += self.chain_frame * res.frame as i32;
+            let chain = features.chain;
+            term!("chain", self.chain, chain);
+            term!("chain_sq", self.chain_sq, chain * chain);
+            term!("chain_score", self.chain_score, features.chain_score / 1000);
+            term!("chain_frame", self.chain_frame, features.chain_frame);
         }
 
         {
             // 盤面から起こりうる連鎖
-            let (main_chain, sub_chain) = detect_chains(cf);
-
-            if let Some((rensa_result, ignition_y)) = main_chain {
-                score += self.potential_main_chain * rensa_result.chain as i32;
-                score +=
-                    self.potential_main_chain * (rensa_result.chain * rensa_result.chain) as i32;
-                score += self.potential_main_chain_frame * rensa_result.frame as i32;
-                score += self.potential_main_chain_ignition_height * ignition_y as i32;
-            }
+            // `*_sq` には（今のところ）`*_sq` ではなく `potential_*_chain` の重みを使っている
+            let chain = features.potential_main_chain;
+            term!("potential_main_chain", self.potential_main_chain, chain);
+            term!(
+                "potential_main_chain_sq" => "potential_main_chain",
+                self.potential_main_chain,
+                chain * chain
+            );
+            term!(
+                "potential_main_chain_frame",
+                self.potential_main_chain_frame,
+                features.potential_main_chain_frame
+            );
+            term!(
+                "potential_main_chain_ignition_height",
+                self.potential_main_chain_ignition_height,
+                features.potential_main_chain_ignition_height
+            );
 
-            if let Some((rensa_result, ignition_y)) = sub_chain {
-                score += self.potential_sub_chain * rensa_result.chain as i32;
-                score +=
-                    self.potential_sub_chain * (rensa_result.chain * rensa_result.chain) as i32;
-                score += self.potential_sub_chain_frame * rensa_result.frame as i32;
-                score += self.potential_sub_chain_ignition_height * ignition_y as i32;
-            }
+            let chain = features.potential_sub_chain;
+            term!("potential_sub_chain", self.potential_sub_chain, chain);
+            term!(
+                "potential_sub_chain_sq" => "potential_sub_chain",
+                self.potential_sub_chain,
+                chain * chain
+            );
+            term!(
+                "potential_sub_chain_frame",
+                self.potential_sub_chain_frame,
+                features.potential_sub_chain_frame
+            );
+            term!(
+                "potential_sub_chain_ignition_height",
+                self.potential_sub_chain_ignition_height,
+                features.potential_sub_chain_ignition_height
+            );
         }
 
         {
             // フレーム関係
-            score += self.chigiri * plan.num_chigiri() as i32;
-            score += self.move_frame * plan.frame() as i32;
+            term!("chigiri", self.chigiri, features.chigiri);
+            term!("move_frame", self.move_frame, features.move_frame);
         }
 
         {
             // パターンマッチング
             macro_rules! pattern_matching {
                 ($name:ident) => {
-                    score += self.$name * $name(cf) as i32;
+                    term!(stringify!($name), self.$name, features.$name as i32);
                 };
             }
 
@@ -351,78 +531,38 @@ impl Evaluator {
     }
 }
 
-/// 各列の平均の高さを返す
-fn average_height(cf: &CoreField) -> f32 {
-    let mut sum = 0;
-    for x in 1..=field::WIDTH {
-        sum += cf.height(x);
-    }
-    sum as f32 / 6.0
-}
+#[cfg(test)]
+mod tests {
+    use puyoai::{color::PuyoColor, field::CoreField, kumipuyo::Kumipuyo};
 
-/// 連結の数を数える
-fn connectivity(cf: &CoreField) -> [i32; 4] {
-    let mut con = [0; 4];
-    for x in 1..=field::WIDTH {
-        for y in 1..=cf.height(x) {
-            // おじゃまなどは飛ばす
-            if !cf.color(x, y).is_normal_color() {
-                continue;
-            }
-            // TODO: すでに計算済みなら飛ばす（puyoai の `countConnectedPuyos`）
-            let cnt = cf.count_connected(x, y);
-            if cnt < 4 {
-                con[cnt] += 1;
-            }
-        }
-    }
-    con
-}
-
-/// 与えられた盤面に対して、`detect_by_drop` で本線と副砲を検出する
-/// - 複数あるなら、連鎖の効率（得点 / フレーム数）が一番良いものを選ぶ
-/// - 本線は5000点以上の連鎖、副砲は5000点未満の連鎖とする
-/// - 返り値は、本線と副砲に対する `(その連鎖の詳細, 発火点の高さ)`
-fn detect_chains(cf: &CoreField) -> (Option<(RensaResult, usize)>, Option<(RensaResult, usize)>) {
-    let mut main_chain: Option<(RensaResult, usize)> = None;
-    let mut sub_chain: Option<(RensaResult, usize)> = None;
-
-    detect_by_drop(
-        &cf,
-        &[false; 8],
-        PurposeForFindingRensa::ForFire,
-        2,
-        13,
-        |complemented_field: CoreField, cpl: &ColumnPuyoList| {
-            // 同列に最大2個補完するので、`[0, 0, 2, 0, 0, 0]` のような感じになるはず
-            let ignition_y = cf.height(
-                (1..=field::WIDTH)
-                    .max_by(|i, j| cpl.size_on(*i).cmp(&cpl.size_on(*j)))
-                    .unwrap(),
-            );
-
-            let rensa_result = complemented_field.clone().simulate();
-            let target_chain_opt = if rensa_result.score >= 5000 {
-                &mut main_chain
-            } else if rensa_result.score >= 70 {
-                // おじゃまを少なくとも1個送れるなら副砲とみなす
-                &mut sub_chain
-            } else {
-                return;
-            };
+    use super::*;
 
-            if let Some((ord_rensa_result, _ord_ignition_y)) = target_chain_opt {
-                // TODO: 同率は処理する？（確率低すぎるのでしなくてよさそう）
-                if ord_rensa_result.score * rensa_result.frame
-                    < rensa_result.score * ord_rensa_result.frame
-                {
-                    *target_chain_opt = Some((rensa_result, ignition_y));
-                }
-            } else {
-                *target_chain_opt = Some((rensa_result, ignition_y));
-            }
-        },
-    );
+    #[test]
+    fn test_explain() {
+        let cf = CoreField::from_str(concat!(
+            "B.....", // 4
+            "RY..G.", // 3
+            "RRYBBG", // 2
+            "BBYGGR"  // 1
+        ));
+        let seq = vec![Kumipuyo::new(PuyoColor::RED, PuyoColor::YELLOW)];
+        let evaluator = Evaluator::default();
 
-    (main_chain, sub_chain)
+        Plan::iterate_available_plans(&cf, &seq, 1, &mut |plan: &Plan| {
+            let explanation = evaluator.explain(plan);
+            assert_eq!(explanation.score, evaluator.evaluate(plan));
+            assert_eq!(
+                explanation.score,
+                explanation
+                    .terms
+                    .iter()
+                    .map(|term| term.contribution)
+                    .sum::<i32>()
+            );
+            assert!(explanation
+                .terms
+                .iter()
+                .any(|term| term.name == "potential_main_chain"));
+        });
+    }
 }
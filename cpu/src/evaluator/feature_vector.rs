@@ -0,0 +1,408 @@
+use puyoai::{
+    color::Color,
+    column_puyo_list::ColumnPuyoList,
+    field::{self, CoreField},
+    plan::Plan,
+    rensa_detector::{detector::detect_by_drop, PurposeForFindingRensa},
+    rensa_result::RensaResult,
+};
+use serde::{Deserialize, Serialize};
+
+use super::detect_shape::*;
+
+/// 特徴量を `(名前, 値)` の列に展開する
+trait PushValues {
+    fn push_values(&self, name: &'static str, values: &mut Vec<(String, f32)>);
+}
+
+impl PushValues for i32 {
+    fn push_values(&self, name: &'static str, values: &mut Vec<(String, f32)>) {
+        values.push((name.to_owned(), *self as f32));
+    }
+}
+
+impl PushValues for f32 {
+    fn push_values(&self, name: &'static str, values: &mut Vec<(String, f32)>) {
+        values.push((name.to_owned(), *self));
+    }
+}
+
+impl PushValues for bool {
+    fn push_values(&self, name: &'static str, values: &mut Vec<(String, f32)>) {
+        values.push((name.to_owned(), *self as i32 as f32));
+    }
+}
+
+impl<T: PushValues, const N: usize> PushValues for [T; N] {
+    fn push_values(&self, name: &'static str, values: &mut Vec<(String, f32)>) {
+        for (i, value) in self.iter().enumerate() {
+            let mut named = vec![];
+            value.push_values(name, &mut named);
+            values.extend(
+                named
+                    .into_iter()
+                    .map(|(name, value)| (format!("{}_{}", name, i + 1), value)),
+            );
+        }
+    }
+}
+
+macro_rules! feature_vector {
+    ($($(#[$meta:meta])* $name:ident: $ty:ty,)*) => {
+        /// 評価関数で使う特徴量（重みとは独立に、盤面と `Plan` だけから決まる）
+        /// - `Evaluator` はこれに重みを掛けて評価値を計算する
+        #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+        pub struct FeatureVector {
+            /// 死んでいる盤面か（その場合、他の特徴量は全て 0）
+            pub is_dead: bool,
+            $($(#[$meta])* pub $name: $ty,)*
+        }
+
+        impl FeatureVector {
+            /// 全ての特徴量を `(名前, 値)` の列にする（オフラインでの学習用）
+            /// - 配列は `top_row_1` のように要素ごとに分ける
+            pub fn named_values(&self) -> Vec<(String, f32)> {
+                let mut values = vec![];
+                self.is_dead.push_values("is_dead", &mut values);
+                $(self.$name.push_values(stringify!($name), &mut values);)*
+                values
+            }
+
+            /// `named_values` の値だけ
+            pub fn values(&self) -> Vec<f32> {
+                self.named_values().into_iter().map(|(_, value)| value).collect()
+            }
+        }
+    };
+}
+
+feature_vector! {
+    // 盤面
+    /// 各列の谷の深さの合計
+    valley: i32,
+    /// 各列の山の高さの合計
+    ridge: i32,
+    /// 各列の平均の高さ
+    average_height: f32,
+    /// 理想の高さ（平均から両端を +2、3・4列目を -2）との差の絶対値の合計
+    ideal_height_diff: f32,
+    /// 理想の高さとの差の 2 乗の合計
+    ideal_height_diff_sq: f32,
+    /// 3列目の高さ
+    third_column_height: i32,
+    /// 到達できない空白の数
+    unreachable_space: i32,
+    /// 各列の 13 段目が埋まっているか
+    top_row: [bool; field::WIDTH],
+    // 連結
+    /// 2 連結のぷよの数
+    connectivity_2: i32,
+    /// 3 連結のぷよの数
+    connectivity_3: i32,
+    // 発生した連鎖
+    /// 連鎖数
+    chain: i32,
+    /// 連鎖の得点
+    chain_score: i32,
+    /// 連鎖にかかるフレーム数
+    chain_frame: i32,
+    // 盤面から起こりうる連鎖（なければ 0）
+    /// 本線の連鎖数
+    potential_main_chain: i32,
+    /// 本線のフレーム数
+    potential_main_chain_frame: i32,
+    /// 本線の発火点の高さ
+    potential_main_chain_ignition_height: i32,
+    /// 副砲の連鎖数
+    potential_sub_chain: i32,
+    /// 副砲のフレーム数
+    potential_sub_chain_frame: i32,
+    /// 副砲の発火点の高さ
+    potential_sub_chain_ignition_height: i32,
+    // フレーム関係
+    /// ちぎった回数
+    chigiri: i32,
+    /// `plan.frame()`
+    move_frame: i32,
+    // パターンマッチング
+    gtr_base_1: bool,
+    gtr_base_2: bool,
+    gtr_base_3: bool,
+    gtr_base_4: bool,
+    gtr_base_5: bool,
+    gtr_base_6: bool,
+    gtr_base_7: bool,
+    gtr_1: bool,
+    gtr_2: bool,
+    gtr_3: bool,
+    gtr_4: bool,
+    gtr_5: bool,
+    gtr_6: bool,
+    gtr_tail_1_1: bool,
+    gtr_tail_1_2: bool,
+    gtr_tail_1_3: bool,
+    gtr_tail_2_1: bool,
+    gtr_tail_2_2: bool,
+    gtr_tail_2_3: bool,
+    gtr_tail_2_4: bool,
+    gtr_tail_2_5: bool,
+    gtr_tail_2_6: bool,
+    gtr_tail_2_7: bool,
+    gtr_tail_3_1: bool,
+    gtr_tail_3_2: bool,
+    gtr_tail_3_3: bool,
+    gtr_tail_3_4: bool,
+    gtr_tail_4_1: bool,
+    gtr_tail_5_1: bool,
+    gtr_tail_5_2: bool,
+    gtr_tail_6_1: bool,
+    gtr_tail_6_2: bool,
+    gtr_tail_6_3: bool,
+    gtr_head_1: bool,
+    gtr_head_2: bool,
+    gtr_head_3: bool,
+    gtr_head_4: bool,
+    gtr_head_5: bool,
+    gtr_head_6: bool,
+}
+
+/// `plan` の特徴量を計算する
+pub fn extract_features(plan: &Plan) -> FeatureVector {
+    let cf = plan.field();
+    let res = plan.rensa_result();
+
+    if cf.is_dead() {
+        return FeatureVector {
+            is_dead: true,
+            ..Default::default()
+        };
+    }
+
+    let mut features = FeatureVector::default();
+
+    {
+        // 盤面
+        for x in 1..=field::WIDTH {
+            features.valley += cf.valley_depth(x) as i32;
+            features.ridge += cf.ridge_height(x) as i32;
+        }
+
+        let average_height = average_height(cf);
+        for x in 1..=field::WIDTH {
+            let ideal_height = average_height
+                + match x {
+                    1 | 6 => 2.0,
+                    3 | 4 => -2.0,
+                    _ => 0.0,
+                };
+
+            let diff = ideal_height - cf.height(x) as f32;
+            features.ideal_height_diff += diff.abs();
+            features.ideal_height_diff_sq += diff * diff;
+        }
+        features.average_height = average_height;
+
+        features.third_column_height = cf.height(3) as i32;
+        features.unreachable_space = cf.count_unreachable_spaces() as i32;
+
+        for x in 1..=field::WIDTH {
+            features.top_row[x - 1] = !cf.is_empty(x, 13);
+        }
+    }
+
+    {
+        // 連結
+        let connectivity = connectivity(cf);
+        features.connectivity_2 = connectivity[2];
+        features.connectivity_3 = connectivity[3];
+    }
+
+    {
+        // 発生した連鎖
+        features.chain = res.chain as i32;
+        features.chain_score = res.score as i32;
+        features.chain_frame = res.frame as i32;
+    }
+
+    {
+        // 盤面から起こりうる連鎖
+        let (main_chain, sub_chain) = detect_chains(cf);
+
+        if let Some((rensa_result, ignition_y)) = main_chain {
+            features.potential_main_chain = rensa_result.chain as i32;
+            features.potential_main_chain_frame = rensa_result.frame as i32;
+            features.potential_main_chain_ignition_height = ignition_y as i32;
+        }
+
+        if let Some((rensa_result, ignition_y)) = sub_chain {
+            features.potential_sub_chain = rensa_result.chain as i32;
+            features.potential_sub_chain_frame = rensa_result.frame as i32;
+            features.potential_sub_chain_ignition_height = ignition_y as i32;
+        }
+    }
+
+    {
+        // フレーム関係
+        features.chigiri = plan.num_chigiri() as i32;
+        features.move_frame = plan.frame() as i32;
+    }
+
+    {
+        // パターンマッチング
+        macro_rules! pattern_matching {
+            ($($name:ident),*) => {
+                $(features.$name = $name(cf);)*
+            };
+        }
+
+        pattern_matching!(
+            gtr_base_1,
+            gtr_base_2,
+            gtr_base_3,
+            gtr_base_4,
+            gtr_base_5,
+            gtr_base_6,
+            gtr_base_7,
+            gtr_1,
+            gtr_2,
+            gtr_3,
+            gtr_4,
+            gtr_5,
+            gtr_6,
+            gtr_tail_1_1,
+            gtr_tail_1_2,
+            gtr_tail_1_3,
+            gtr_tail_2_1,
+            gtr_tail_2_2,
+            gtr_tail_2_3,
+            gtr_tail_2_4,
+            gtr_tail_2_5,
+            gtr_tail_2_6,
+            gtr_tail_2_7,
+            gtr_tail_3_1,
+            gtr_tail_3_2,
+            gtr_tail_3_3,
+            gtr_tail_3_4,
+            gtr_tail_4_1,
+            gtr_tail_5_1,
+            gtr_tail_5_2,
+            gtr_tail_6_1,
+            gtr_tail_6_2,
+            gtr_tail_6_3,
+            gtr_head_1,
+            gtr_head_2,
+            gtr_head_3,
+            gtr_head_4,
+            gtr_head_5,
+            gtr_head_6
+        );
+    }
+
+    features
+}
+
+/// 各列の平均の高さを返す
+fn average_height(cf: &CoreField) -> f32 {
+    let mut sum = 0;
+    for x in 1..=field::WIDTH {
+        sum += cf.height(x);
+    }
+    sum as f32 / 6.0
+}
+
+/// 連結の数を数える
+fn connectivity(cf: &CoreField) -> [i32; 4] {
+    let mut con = [0; 4];
+    for x in 1..=field::WIDTH {
+        for y in 1..=cf.height(x) {
+            // おじゃまなどは飛ばす
+            if !cf.color(x, y).is_normal_color() {
+                continue;
+            }
+            // TODO: すでに計算済みなら飛ばす（puyoai の `countConnectedPuyos`）
+            let cnt = cf.count_connected(x, y);
+            if cnt < 4 {
+                con[cnt] += 1;
+            }
+        }
+    }
+    con
+}
+
+/// 与えられた盤面に対して、`detect_by_drop` で本線と副砲を検出する
+/// - 複数あるなら、連鎖の効率（得点 / フレーム数）が一番良いものを選ぶ
+/// - 本線は5000点以上の連鎖、副砲は5000点未満の連鎖とする
+/// - 返り値は、本線と副砲に対する `(その連鎖の詳細, 発火点の高さ)`
+fn detect_chains(cf: &CoreField) -> (Option<(RensaResult, usize)>, Option<(RensaResult, usize)>) {
+    let mut main_chain: Option<(RensaResult, usize)> = None;
+    let mut sub_chain: Option<(RensaResult, usize)> = None;
+
+    detect_by_drop(
+        &cf,
+        &[false; 8],
+        PurposeForFindingRensa::ForFire,
+        2,
+        13,
+        |complemented_field: CoreField, cpl: &ColumnPuyoList| {
+            // 同列に最大2個補完するので、`[0, 0, 2, 0, 0, 0]` のような感じになるはず
+            let ignition_y = cf.height(
+                (1..=field::WIDTH)
+                    .max_by(|i, j| cpl.size_on(*i).cmp(&cpl.size_on(*j)))
+                    .unwrap(),
+            );
+
+            let rensa_result = complemented_field.clone().simulate();
+            let target_chain_opt = if rensa_result.score >= 5000 {
+                &mut main_chain
+            } else if rensa_result.score >= 70 {
+                // おじゃまを少なくとも1個送れるなら副砲とみなす
+                &mut sub_chain
+            } else {
+                return;
+            };
+
+            if let Some((ord_rensa_result, _ord_ignition_y)) = target_chain_opt {
+                // TODO: 同率は処理する？（確率低すぎるのでしなくてよさそう）
+                if ord_rensa_result.score * rensa_result.frame
+                    < rensa_result.score * ord_rensa_result.frame
+                {
+                    *target_chain_opt = Some((rensa_result, ignition_y));
+                }
+            } else {
+                *target_chain_opt = Some((rensa_result, ignition_y));
+            }
+        },
+    );
+
+    (main_chain, sub_chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use puyoai::{color::PuyoColor, kumipuyo::Kumipuyo};
+
+    use super::*;
+
+    #[test]
+    fn test_extract_features() {
+        let cf = CoreField::from_str(concat!(
+            "B.....", // 4
+            "RY..G.", // 3
+            "RRYBBG", // 2
+            "BBYGGR"  // 1
+        ));
+        let seq = vec![Kumipuyo::new(PuyoColor::RED, PuyoColor::YELLOW)];
+
+        Plan::iterate_available_plans(&cf, &seq, 1, &mut |plan: &Plan| {
+            let features = extract_features(plan);
+            assert!(!features.is_dead);
+            assert_eq!(features.third_column_height, plan.field().height(3) as i32);
+            assert_eq!(features.chain, plan.chain() as i32);
+
+            let named_values = features.named_values();
+            assert_eq!(named_values.len(), features.values().len());
+            assert!(named_values.iter().any(|(name, _)| name == "top_row_6"));
+            assert!(named_values.iter().any(|(name, _)| name == "gtr_head_6"));
+        });
+    }
+}
@@ -1,3 +1,4 @@
 pub mod bot;
 pub mod evaluator;
 pub mod opening_matcher;
+pub mod upi;
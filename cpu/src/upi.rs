@@ -0,0 +1,4 @@
+pub mod pfen;
+pub mod upi_command;
+
+pub use upi_command::{parse_upi_command, UpiCommand, UpiPosition};
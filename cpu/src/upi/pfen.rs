@@ -0,0 +1,179 @@
+//! UPI で使う pfen 形式の文字列と、盤面・ツモ・指し手との相互変換
+//! （pfen文字列: https://github.com/TukamotoRyuzo/upi-protocol/blob/master/README.md）
+
+use puyoai::{
+    color::PuyoColor,
+    decision::Decision,
+    field::{self, CoreField},
+    kumipuyo::Kumipuyo,
+};
+
+/// pfen の文字を色に変換
+fn color_from_char(c: char) -> Result<PuyoColor, String> {
+    match c {
+        'r' => Ok(PuyoColor::RED),
+        'b' => Ok(PuyoColor::BLUE),
+        'y' => Ok(PuyoColor::YELLOW),
+        'g' => Ok(PuyoColor::GREEN),
+        'o' => Ok(PuyoColor::OJAMA),
+        _ => Err(format!("invalid puyo: {}", c)),
+    }
+}
+
+/// 色を pfen の文字に変換
+fn color_to_char(color: PuyoColor) -> char {
+    match color {
+        PuyoColor::RED => 'r',
+        PuyoColor::BLUE => 'b',
+        PuyoColor::YELLOW => 'y',
+        PuyoColor::GREEN => 'g',
+        PuyoColor::OJAMA => 'o',
+        _ => unreachable!(),
+    }
+}
+
+/// pfen の盤面（1列目から順に、下から上へ。列は `/` 区切り）を `CoreField` に変換
+pub fn parse_pfen_field(pfen: &str) -> Result<CoreField, String> {
+    let columns = pfen.trim_end_matches('/').split('/').collect::<Vec<&str>>();
+    if columns.len() > field::WIDTH {
+        return Err(format!("too many columns: {}", pfen));
+    }
+
+    let mut cf = CoreField::new();
+    for (x, column) in columns.iter().enumerate() {
+        if column.len() > 14 {
+            return Err(format!("too many puyos in column {}: {}", x + 1, pfen));
+        }
+        for (y, c) in column.chars().enumerate() {
+            cf.field_mut().set_color(x + 1, y + 1, color_from_char(c)?);
+        }
+    }
+    cf.update_height();
+    Ok(cf)
+}
+
+/// `CoreField` を pfen の盤面に変換
+pub fn format_pfen_field(cf: &CoreField) -> String {
+    let mut ret = String::new();
+    for x in 1..=field::WIDTH {
+        for y in 1..=cf.height(x) {
+            ret.push(color_to_char(cf.color(x, y)));
+        }
+        ret.push('/');
+    }
+    ret
+}
+
+/// pfen のツモ（軸ぷよ・子ぷよの順。組ぷよは `/` 区切り）を変換
+pub fn parse_pfen_tumos(pfen: &str) -> Result<Vec<Kumipuyo>, String> {
+    let mut seq = vec![];
+    for tumo in pfen.split('/').filter(|tumo| !tumo.is_empty()) {
+        let chars = tumo.chars().collect::<Vec<char>>();
+        if chars.len() != 2 {
+            return Err(format!("invalid tumo: {}", tumo));
+        }
+        let axis = color_from_char(chars[0])?;
+        let child = color_from_char(chars[1])?;
+        if axis == PuyoColor::OJAMA || child == PuyoColor::OJAMA {
+            return Err(format!("invalid tumo: {}", tumo));
+        }
+        seq.push(Kumipuyo::new(axis, child));
+    }
+    Ok(seq)
+}
+
+/// ツモを pfen の形式に変換
+pub fn format_pfen_tumos(seq: &Vec<Kumipuyo>) -> String {
+    let mut ret = String::new();
+    for kumipuyo in seq {
+        ret.push(color_to_char(kumipuyo.axis()));
+        ret.push(color_to_char(kumipuyo.child()));
+        ret.push('/');
+    }
+    ret
+}
+
+/// 子ぷよの向き（`Decision::rot` の順）
+const ROT_CHARS: [char; 4] = ['u', 'r', 'd', 'l'];
+
+/// 指し手を `<軸ぷよの列><子ぷよの向き>` の形式に変換（例: `3u`, `4l`）
+/// - 子ぷよの向きは上から時計回りに `u`, `r`, `d`, `l`
+pub fn format_move(decision: &Decision) -> String {
+    format!("{}{}", decision.axis_x(), ROT_CHARS[decision.rot()])
+}
+
+/// `format_move` の形式の指し手を `Decision` に変換
+pub fn parse_move(s: &str) -> Result<Decision, String> {
+    let chars = s.chars().collect::<Vec<char>>();
+    if chars.len() != 2 {
+        return Err(format!("invalid move: {}", s));
+    }
+    let x = chars[0]
+        .to_digit(10)
+        .map(|x| x as usize)
+        .filter(|x| (1..=field::WIDTH).contains(x))
+        .ok_or_else(|| format!("invalid move: {}", s))?;
+    let r = ROT_CHARS
+        .iter()
+        .position(|&c| c == chars[1])
+        .ok_or_else(|| format!("invalid move: {}", s))?;
+
+    let decision = Decision::new(x, r);
+    if !Decision::all_valid_decisions().contains(&decision) {
+        return Err(format!("invalid move: {}", s));
+    }
+    Ok(decision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pfen_field() {
+        assert_eq!(parse_pfen_field("//////").unwrap(), CoreField::new());
+        assert_eq!(parse_pfen_field("").unwrap(), CoreField::new());
+
+        let cf = CoreField::from_str(concat!(
+            "G.....", // 4
+            "Y....B", // 3
+            "GR...O", // 2
+            "RRB.YG"  // 1
+        ));
+        assert_eq!(parse_pfen_field("rgyg/rr/b//y/gob/").unwrap(), cf);
+        assert_eq!(parse_pfen_field("rgyg/rr/b//y/gob").unwrap(), cf);
+        assert_eq!(format_pfen_field(&cf), "rgyg/rr/b//y/gob/");
+
+        assert!(parse_pfen_field("rgyg/rr/b//y/gob/r/").is_err());
+        assert!(parse_pfen_field("rgpg/////").is_err());
+    }
+
+    #[test]
+    fn test_parse_pfen_tumos() {
+        let seq = vec![
+            Kumipuyo::new(PuyoColor::RED, PuyoColor::BLUE),
+            Kumipuyo::new(PuyoColor::YELLOW, PuyoColor::YELLOW),
+        ];
+        assert_eq!(parse_pfen_tumos("rb/yy/").unwrap(), seq);
+        assert_eq!(parse_pfen_tumos("rb/yy").unwrap(), seq);
+        assert_eq!(format_pfen_tumos(&seq), "rb/yy/");
+
+        assert!(parse_pfen_tumos("rby/yy/").is_err());
+        assert!(parse_pfen_tumos("ro/yy/").is_err());
+    }
+
+    #[test]
+    fn test_move() {
+        for decision in Decision::all_valid_decisions() {
+            assert_eq!(&parse_move(&format_move(decision)).unwrap(), decision);
+        }
+
+        assert_eq!(format_move(&Decision::new(3, 0)), "3u");
+        assert_eq!(format_move(&Decision::new(4, 3)), "4l");
+
+        assert!(parse_move("1l").is_err());
+        assert!(parse_move("6r").is_err());
+        assert!(parse_move("7u").is_err());
+        assert!(parse_move("3x").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use puyoai::{color::Color, es_frame, field, field::CoreField};
+
+use super::pfen::{parse_pfen_field, parse_pfen_tumos};
+use crate::bot::PlayerState;
+
+/// GUI から送られてくる UPI のコマンド
+pub enum UpiCommand {
+    /// `upi`
+    Upi,
+    /// `isready`
+    IsReady,
+    /// `upinewgame`
+    UpiNewGame,
+    /// `position pfen <盤面> <ツモ> [<おじゃま>] [<盤面> <ツモ> <おじゃま>]`
+    Position(UpiPosition),
+    /// `go`
+    Go,
+    /// `stop`
+    Stop,
+    /// `quit`
+    Quit,
+}
+
+/// `position` コマンドで渡された局面
+pub struct UpiPosition {
+    pub player_state_1p: PlayerState,
+    pub player_state_2p: Option<PlayerState>,
+}
+
+/// 1行分のコマンドをパースする
+pub fn parse_upi_command(line: &str) -> Result<UpiCommand, String> {
+    let tokens = line.split_whitespace().collect::<Vec<&str>>();
+    match tokens.as_slice() {
+        ["upi"] => Ok(UpiCommand::Upi),
+        ["isready"] => Ok(UpiCommand::IsReady),
+        ["upinewgame"] => Ok(UpiCommand::UpiNewGame),
+        ["position", "pfen", args @ ..] => Ok(UpiCommand::Position(parse_position(args)?)),
+        ["go", ..] => Ok(UpiCommand::Go),
+        ["stop"] => Ok(UpiCommand::Stop),
+        ["quit"] => Ok(UpiCommand::Quit),
+        _ => Err(format!("unknown command: {}", line)),
+    }
+}
+
+/// `position pfen` 以降をパースする
+/// - 1P のみ: `<盤面> <ツモ> [<おじゃま>]`
+/// - 2P も含む: `<盤面> <ツモ> <おじゃま> <盤面> <ツモ> <おじゃま>`
+fn parse_position(args: &[&str]) -> Result<UpiPosition, String> {
+    match args.len() {
+        2 | 3 => Ok(UpiPosition {
+            player_state_1p: parse_player_state(args)?,
+            player_state_2p: None,
+        }),
+        6 => Ok(UpiPosition {
+            player_state_1p: parse_player_state(&args[0..3])?,
+            player_state_2p: Some(parse_player_state(&args[3..6])?),
+        }),
+        _ => Err(format!("invalid position: {}", args.join(" "))),
+    }
+}
+
+/// 局面として受け付けるツモの最小数（今のツモとネクスト）
+const MIN_TUMOS: usize = 2;
+
+fn parse_player_state(args: &[&str]) -> Result<PlayerState, String> {
+    let field = parse_pfen_field(args[0])?;
+    let seq = parse_pfen_tumos(args[1])?;
+    if seq.len() < MIN_TUMOS {
+        return Err(format!(
+            "at least {} tumos are required: {}",
+            MIN_TUMOS, args[1]
+        ));
+    }
+    let fixed_ojama = match args.get(2) {
+        Some(ojama) => ojama
+            .parse::<usize>()
+            .map_err(|_| format!("invalid ojama: {}", ojama))?,
+        None => 0,
+    };
+    let tumo_index = estimate_tumo_index(&field);
+
+    Ok(PlayerState::new(
+        0,
+        field,
+        seq,
+        0,
+        0,
+        fixed_ojama,
+        0,
+        0,
+        tumo_index,
+        es_frame::OJAMA_PUYO_RATES[0],
+        false,
+        None,
+    ))
+}
+
+/// ツモ番号は渡されないので、盤面上の色ぷよの数から推定する
+/// （序盤のテンプレを使うかの判定にしか使われていないので、これで十分）
+fn estimate_tumo_index(cf: &CoreField) -> usize {
+    let mut count = 0;
+    for x in 1..=field::WIDTH {
+        for y in 1..=cf.height(x) {
+            if cf.color(x, y).is_normal_color() {
+                count += 1;
+            }
+        }
+    }
+    count / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use puyoai::{color::PuyoColor, kumipuyo::Kumipuyo};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_upi_command() {
+        assert!(matches!(parse_upi_command("upi"), Ok(UpiCommand::Upi)));
+        assert!(matches!(
+            parse_upi_command("isready"),
+            Ok(UpiCommand::IsReady)
+        ));
+        assert!(matches!(
+            parse_upi_command("upinewgame"),
+            Ok(UpiCommand::UpiNewGame)
+        ));
+        assert!(matches!(parse_upi_command("go"), Ok(UpiCommand::Go)));
+        assert!(matches!(parse_upi_command("  quit "), Ok(UpiCommand::Quit)));
+        assert!(parse_upi_command("hello").is_err());
+        assert!(parse_upi_command("position pfen").is_err());
+    }
+
+    #[test]
+    fn test_parse_position() {
+        let command = parse_upi_command("position pfen rr/gg//// by/rr/ 3").unwrap();
+        if let UpiCommand::Position(position) = command {
+            let state = position.player_state_1p;
+            assert_eq!(
+                state.field,
+                CoreField::from_str(concat!(
+                    "RG....", // 2
+                    "RG...."  // 1
+                ))
+            );
+            assert_eq!(
+                state.seq,
+                vec![
+                    Kumipuyo::new(PuyoColor::BLUE, PuyoColor::YELLOW),
+                    Kumipuyo::new(PuyoColor::RED, PuyoColor::RED),
+                ]
+            );
+            assert_eq!(state.fixed_ojama, 3);
+            assert_eq!(state.tumo_index, 2);
+            assert!(position.player_state_2p.is_none());
+        } else {
+            panic!("not a position command");
+        }
+
+        let command =
+            parse_upi_command("position pfen ////// rr/gg/ 0 oo/o//// bb/yy/ 12").unwrap();
+        if let UpiCommand::Position(position) = command {
+            let state = position.player_state_2p.unwrap();
+            assert_eq!(state.field.height(1), 2);
+            assert_eq!(state.field.height(2), 1);
+            assert_eq!(state.fixed_ojama, 12);
+            assert_eq!(state.tumo_index, 0);
+        } else {
+            panic!("not a position command");
+        }
+
+        assert!(parse_upi_command("position pfen ////// rr/gg/ x").is_err());
+        assert!(parse_upi_command("position pfen ////// rr/gg/ 0 //////").is_err());
+        // ツモが足りない
+        assert!(parse_upi_command("position pfen ////// /").is_err());
+        assert!(parse_upi_command("position pfen ////// rr/").is_err());
+        assert!(parse_upi_command("position pfen ////// rr/gg/ 0 ////// bb/ 0").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use rand::prelude::*;
+
+/// パラメータの 1 つ 1 つの値（遺伝子）
+pub trait Gene: Copy {
+    /// `min..=max` から一様に選ぶ
+    fn generate<R: Rng + ?Sized>(min: Self, max: Self, rng: &mut R) -> Self;
+    /// どちらかの親の値か平均を取り（たまに `min..=max` から選び直す）、
+    /// `-noise..=noise` だけずらしたものを `min..=max` に収めて返す
+    fn crossover_with_noise<R: Rng + ?Sized>(
+        v1: Self,
+        v2: Self,
+        min: Self,
+        max: Self,
+        noise: Self,
+        rng: &mut R,
+    ) -> Self;
+    /// `crossover_with_noise` で、幅の 1% 程度ずらす
+    fn crossover<R: Rng + ?Sized>(v1: Self, v2: Self, min: Self, max: Self, rng: &mut R) -> Self;
+    fn to_i64(self) -> i64;
+}
+
+macro_rules! impl_gene {
+    ($($ty:ty),*) => {
+        $(
+            impl Gene for $ty {
+                fn generate<R: Rng + ?Sized>(min: Self, max: Self, rng: &mut R) -> Self {
+                    rng.gen_range(min..=max)
+                }
+
+                fn crossover_with_noise<R: Rng + ?Sized>(
+                    v1: Self,
+                    v2: Self,
+                    min: Self,
+                    max: Self,
+                    noise: Self,
+                    rng: &mut R,
+                ) -> Self {
+                    let v = match rng.gen_range(0..100) {
+                        0..=41 => v1 as i64,                        // 42%
+                        42..=83 => v2 as i64,                       // 42%
+                        84..=98 => (v1 as i64 + v2 as i64) / 2,     // 15%
+                        _ => Self::generate(min, max, rng) as i64,
+                    };
+                    let noise = noise as i64;
+                    let v = v + rng.gen_range(-noise..=noise);
+                    v.max(min as i64).min(max as i64) as Self
+                }
+
+                fn crossover<R: Rng + ?Sized>(
+                    v1: Self,
+                    v2: Self,
+                    min: Self,
+                    max: Self,
+                    rng: &mut R,
+                ) -> Self {
+                    let noise = ((max as i64 - min as i64) / 100).max(1) as Self;
+                    Self::crossover_with_noise(v1, v2, min, max, noise, rng)
+                }
+
+                fn to_i64(self) -> i64 {
+                    self as i64
+                }
+            }
+        )*
+    };
+}
+
+impl_gene!(i32, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossover() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let v = i32::crossover(-999, 999, -999, 999, &mut rng);
+            assert!((-999..=999).contains(&v));
+
+            let v = usize::crossover(1, 5, 1, 5, &mut rng);
+            assert!((1..=5).contains(&v));
+
+            let v = i32::crossover_with_noise(-999, -999, -999, 999, 10, &mut rng);
+            assert!((-999..=999).contains(&v));
+        }
+    }
+}
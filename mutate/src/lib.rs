@@ -0,0 +1,134 @@
+//! 遺伝的アルゴリズムでチューニングするパラメータ
+//! - `#[derive(Mutateable)]` と各フィールドの `#[mutate(...)]` で、`generate` と `crossover` を自動で実装する
+
+// derive したコードは `::mutate::...` を参照するので、このクレート内のテストでも使えるように
+extern crate self as mutate;
+
+pub mod gene;
+
+pub use gene::Gene;
+pub use mutate_derive::Mutateable;
+pub use rand::Rng;
+
+/// 乱数は全て `rng` から取る（同じシードの `rng` なら同じ個体になる）
+pub trait Mutateable: Default {
+    fn generate<R: Rng + ?Sized>(sub_name: String, rng: &mut R) -> Self;
+    fn crossover<R: Rng + ?Sized>(
+        parent1: &Self,
+        parent2: &Self,
+        sub_name: String,
+        rng: &mut R,
+    ) -> Self;
+    fn name(&self) -> &str;
+    /// `generate` で選ぶ値の (名前, 最小値, 最大値)（配列は `top_row_1` のように要素ごと）
+    fn parameter_ranges() -> Vec<(String, i64, i64)>;
+    /// `crossover` で収める値の (名前, 最小値, 最大値)（チューニングで取りうる範囲）
+    fn parameter_bounds() -> Vec<(String, i64, i64)>;
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[derive(Default, Mutateable)]
+    struct Parameter {
+        #[mutate(range = -999..1000)]
+        a: i32,
+        #[mutate(range = 1..=5)]
+        b: usize,
+        #[mutate(range = 0..10)]
+        c: [i32; 3],
+        #[mutate(ranges = [-10..0, 0..=10])]
+        d: [i32; 2],
+        #[mutate(skip)]
+        e: bool,
+        #[mutate(sub_name)]
+        sub_name: Option<String>,
+    }
+
+    #[derive(Default, Mutateable)]
+    #[mutate(crossover(range = -999..1000, noise = 10))]
+    struct WideCrossover {
+        #[mutate(range = 0..10)]
+        a: i32,
+    }
+
+    fn assert_in_range(parameter: &Parameter) {
+        assert!((-999..1000).contains(&parameter.a));
+        assert!((1..=5).contains(&parameter.b));
+        assert!(parameter.c.iter().all(|c| (0..10).contains(c)));
+        assert!((-10..0).contains(&parameter.d[0]));
+        assert!((0..=10).contains(&parameter.d[1]));
+        assert!(!parameter.e);
+    }
+
+    #[test]
+    fn test_derive() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let parent1 = Parameter::generate("p1".into(), &mut rng);
+            let parent2 = Parameter::generate("p2".into(), &mut rng);
+            assert_in_range(&parent1);
+            assert_in_range(&parent2);
+            assert_eq!(parent1.name(), "p1");
+
+            let child = Parameter::crossover(&parent1, &parent2, "child".into(), &mut rng);
+            assert_in_range(&child);
+            assert_eq!(child.name(), "child");
+        }
+        assert_eq!(Parameter::default().name(), "");
+    }
+
+    #[test]
+    fn test_parameter_ranges() {
+        let ranges = Parameter::parameter_ranges();
+        assert_eq!(ranges.len(), 1 + 1 + 3 + 2);
+        assert_eq!(ranges[0], ("a".to_string(), -999, 999));
+        assert_eq!(ranges[1], ("b".to_string(), 1, 5));
+        assert_eq!(ranges[4], ("c_3".to_string(), 0, 9));
+        assert_eq!(ranges[6], ("d_2".to_string(), 0, 10));
+    }
+
+    #[test]
+    fn test_parameter_bounds() {
+        // 構造体に交叉の範囲がなければ、フィールドの範囲と同じ
+        assert_eq!(Parameter::parameter_bounds(), Parameter::parameter_ranges());
+        assert_eq!(
+            WideCrossover::parameter_ranges(),
+            vec![("a".to_string(), 0, 9)]
+        );
+        assert_eq!(
+            WideCrossover::parameter_bounds(),
+            vec![("a".to_string(), -999, 999)]
+        );
+    }
+
+    #[test]
+    fn test_derive_crossover_options() {
+        // 交叉ではフィールドの範囲ではなく、構造体に指定した範囲に収める
+        let mut rng = StdRng::seed_from_u64(0);
+        let parent = WideCrossover { a: 500 };
+        let children: Vec<_> = (0..100)
+            .map(|_| WideCrossover::crossover(&parent, &parent, String::new(), &mut rng).a)
+            .collect();
+        assert!(children.iter().all(|a| (-999..=999).contains(a)));
+        assert!(children.iter().any(|a| *a >= 10));
+    }
+
+    #[test]
+    fn test_derive_with_seed() {
+        let generate = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let parent1 = Parameter::generate("p1".into(), &mut rng);
+            let parent2 = Parameter::generate("p2".into(), &mut rng);
+            Parameter::crossover(&parent1, &parent2, "child".into(), &mut rng)
+        };
+        let (child1, child2) = (generate(42), generate(42));
+        assert_eq!(
+            (child1.a, child1.b, child1.c, child1.d),
+            (child2.a, child2.b, child2.c, child2.d)
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! `#[derive(Mutateable)]`
+//! - `#[mutate(range = -999..1000)]`: `generate` ではこの範囲から選び、`crossover` でもこの範囲に収める
+//!   （配列なら全ての要素に同じ範囲を使う）
+//! - `#[mutate(ranges = [-999..0, 0..=10])]`: 配列の要素ごとの範囲
+//! - `#[mutate(sub_name)]`: `Option<String>` のフィールドで、個体の名前として使う
+//! - `#[mutate(skip)]`: チューニングしない（`generate` では `Default` の値、`crossover` では 1 つ目の親の値）
+//!
+//! 構造体に `#[mutate(crossover(range = -999..1000, noise = 10))]` を付けると、`crossover` では
+//! 各フィールドの範囲の代わりに全てのフィールドでこの範囲を使い、`-noise..=noise` だけずらす
+//!
+//! `parameter_ranges` は `generate` の範囲、`parameter_bounds` は `crossover` で収める範囲を返す
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse::ParseStream, parse_macro_input, punctuated::Punctuated, spanned::Spanned, Data,
+    DeriveInput, Error, Expr, ExprRange, Fields, Ident, RangeLimits, Token, Type,
+};
+
+#[proc_macro_derive(Mutateable, attributes(mutate))]
+pub fn derive_mutateable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// `min..=max`
+struct Bounds {
+    min: TokenStream2,
+    max: TokenStream2,
+}
+
+enum Kind {
+    Range(Bounds),
+    Ranges(Vec<Bounds>),
+    SubName,
+    Skip,
+}
+
+/// 構造体の `#[mutate(crossover(...))]`
+struct CrossoverOptions {
+    bounds: Bounds,
+    noise: Expr,
+}
+
+impl CrossoverOptions {
+    /// 1 つの値の `crossover`（`bounds` はフィールドの範囲）
+    fn crossover(
+        options: &Option<CrossoverOptions>,
+        v1: TokenStream2,
+        v2: TokenStream2,
+        bounds: &Bounds,
+    ) -> TokenStream2 {
+        match options {
+            Some(CrossoverOptions {
+                bounds: Bounds { min, max },
+                noise,
+            }) => quote! {
+                ::mutate::Gene::crossover_with_noise(#v1, #v2, #min, #max, (#noise), rng)
+            },
+            None => {
+                let Bounds { min, max } = bounds;
+                quote! { ::mutate::Gene::crossover(#v1, #v2, #min, #max, rng) }
+            }
+        }
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new(input.span(), "expected named fields")),
+        },
+        _ => return Err(Error::new(input.span(), "expected a struct")),
+    };
+    let options = parse_crossover_options(input)?;
+
+    let mut generate = vec![];
+    let mut crossover = vec![];
+    let mut ranges = vec![];
+    let mut bounds_list = vec![];
+    let mut sub_name_field: Option<&Ident> = None;
+    let mut uses_rng = false;
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let (is_array, gene_ty) = match &field.ty {
+            Type::Array(array) => (true, &*array.elem),
+            ty => (false, ty),
+        };
+        let to_i64 = |value: &TokenStream2| quote! { <#gene_ty as ::mutate::Gene>::to_i64(#value) };
+        // `crossover` で収める範囲（構造体に指定があればそちら）
+        let crossover_i64 = |bounds: &Bounds| {
+            let Bounds { min, max } = match &options {
+                Some(options) => &options.bounds,
+                None => bounds,
+            };
+            (to_i64(min), to_i64(max))
+        };
+
+        let kind = parse_kind(field)?;
+        uses_rng |= matches!(kind, Kind::Range(_) | Kind::Ranges(_));
+        let (generate_value, crossover_value) = match kind {
+            Kind::Range(bounds) if is_array => {
+                let Bounds { min, max } = &bounds;
+                let crossover = CrossoverOptions::crossover(
+                    &options,
+                    quote! { parent1.#ident[i] },
+                    quote! { parent2.#ident[i] },
+                    &bounds,
+                );
+                let (min_i64, max_i64) = (to_i64(min), to_i64(max));
+                let (bound_min, bound_max) = crossover_i64(&bounds);
+                let len = quote! { <Self as ::core::default::Default>::default().#ident.len() };
+                ranges.push(quote! {
+                    for i in 0..#len {
+                        ranges.push((::std::format!("{}_{}", #name, i + 1), #min_i64, #max_i64));
+                    }
+                });
+                bounds_list.push(quote! {
+                    for i in 0..#len {
+                        ranges.push((::std::format!("{}_{}", #name, i + 1), #bound_min, #bound_max));
+                    }
+                });
+                (
+                    quote! { ::core::array::from_fn(|_| ::mutate::Gene::generate(#min, #max, rng)) },
+                    quote! { ::core::array::from_fn(|i| #crossover) },
+                )
+            }
+            Kind::Range(bounds) => {
+                let Bounds { min, max } = &bounds;
+                let crossover = CrossoverOptions::crossover(
+                    &options,
+                    quote! { parent1.#ident },
+                    quote! { parent2.#ident },
+                    &bounds,
+                );
+                let (min_i64, max_i64) = (to_i64(min), to_i64(max));
+                let (bound_min, bound_max) = crossover_i64(&bounds);
+                ranges.push(quote! {
+                    ranges.push((::std::string::String::from(#name), #min_i64, #max_i64));
+                });
+                bounds_list.push(quote! {
+                    ranges.push((::std::string::String::from(#name), #bound_min, #bound_max));
+                });
+                (
+                    quote! { ::mutate::Gene::generate(#min, #max, rng) },
+                    crossover,
+                )
+            }
+            Kind::Ranges(bounds) => {
+                if !is_array {
+                    return Err(Error::new(field.span(), "`ranges` is only for arrays"));
+                }
+                let generate = bounds.iter().map(|Bounds { min, max }| {
+                    quote! { ::mutate::Gene::generate(#min, #max, rng) }
+                });
+                let crossover = bounds.iter().enumerate().map(|(i, bounds)| {
+                    CrossoverOptions::crossover(
+                        &options,
+                        quote! { parent1.#ident[#i] },
+                        quote! { parent2.#ident[#i] },
+                        bounds,
+                    )
+                });
+                for (i, element) in bounds.iter().enumerate() {
+                    let name = format!("{}_{}", name, i + 1);
+                    let (min_i64, max_i64) = (to_i64(&element.min), to_i64(&element.max));
+                    let (bound_min, bound_max) = crossover_i64(element);
+                    ranges.push(quote! {
+                        ranges.push((::std::string::String::from(#name), #min_i64, #max_i64));
+                    });
+                    bounds_list.push(quote! {
+                        ranges.push((::std::string::String::from(#name), #bound_min, #bound_max));
+                    });
+                }
+                (quote! { [#(#generate),*] }, quote! { [#(#crossover),*] })
+            }
+            Kind::SubName => {
+                if sub_name_field.is_some() {
+                    return Err(Error::new(field.span(), "duplicate `sub_name`"));
+                }
+                sub_name_field = Some(ident);
+                (
+                    quote! { ::core::option::Option::Some(sub_name) },
+                    quote! { ::core::option::Option::Some(sub_name) },
+                )
+            }
+            Kind::Skip => (
+                quote! { <Self as ::core::default::Default>::default().#ident },
+                quote! { ::core::clone::Clone::clone(&parent1.#ident) },
+            ),
+        };
+        generate.push(quote! { #ident: #generate_value });
+        crossover.push(quote! { #ident: #crossover_value });
+    }
+
+    let (name, unused_sub_name) = match sub_name_field {
+        Some(ident) => (quote! { self.#ident.as_deref().unwrap_or("") }, quote! {}),
+        None => (quote! { "" }, quote! { let _ = sub_name; }),
+    };
+    let unused_rng = if uses_rng {
+        quote! {}
+    } else {
+        quote! { let _ = rng; }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mutate::Mutateable for #ident #ty_generics #where_clause {
+            fn generate<R: ::mutate::Rng + ?Sized>(
+                sub_name: ::std::string::String,
+                rng: &mut R,
+            ) -> Self {
+                #unused_sub_name
+                #unused_rng
+                Self {
+                    #(#generate,)*
+                }
+            }
+
+            fn crossover<R: ::mutate::Rng + ?Sized>(
+                parent1: &Self,
+                parent2: &Self,
+                sub_name: ::std::string::String,
+                rng: &mut R,
+            ) -> Self {
+                #unused_sub_name
+                #unused_rng
+                Self {
+                    #(#crossover,)*
+                }
+            }
+
+            fn name(&self) -> &str {
+                #name
+            }
+
+            fn parameter_ranges() -> ::std::vec::Vec<(::std::string::String, i64, i64)> {
+                #[allow(unused_mut)]
+                let mut ranges = ::std::vec::Vec::new();
+                #(#ranges)*
+                ranges
+            }
+
+            fn parameter_bounds() -> ::std::vec::Vec<(::std::string::String, i64, i64)> {
+                #[allow(unused_mut)]
+                let mut ranges = ::std::vec::Vec::new();
+                #(#bounds_list)*
+                ranges
+            }
+        }
+    })
+}
+
+/// 構造体の `#[mutate(crossover(range = ..., noise = ...))]` を読む
+fn parse_crossover_options(input: &DeriveInput) -> Result<Option<CrossoverOptions>, Error> {
+    let attr = match input.attrs.iter().find(|attr| attr.path.is_ident("mutate")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    attr.parse_args_with(|input: ParseStream| {
+        let key: Ident = input.parse()?;
+        if key != "crossover" {
+            return Err(Error::new(key.span(), "unknown mutate attribute"));
+        }
+        let content;
+        syn::parenthesized!(content in input);
+
+        let (mut bounds, mut noise) = (None, None);
+        while !content.is_empty() {
+            let key: Ident = content.parse()?;
+            content.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "range" => bounds = Some(parse_bounds(&content.parse()?)?),
+                "noise" => noise = Some(content.parse::<Expr>()?),
+                _ => return Err(Error::new(key.span(), "unknown crossover option")),
+            }
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+
+        match (bounds, noise) {
+            (Some(bounds), Some(noise)) => Ok(Some(CrossoverOptions { bounds, noise })),
+            _ => Err(Error::new(
+                key.span(),
+                "`crossover` needs both `range` and `noise`",
+            )),
+        }
+    })
+}
+
+/// フィールドの `#[mutate(...)]` を読む
+fn parse_kind(field: &syn::Field) -> Result<Kind, Error> {
+    let attr = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("mutate"))
+        .ok_or_else(|| {
+            Error::new(
+                field.span(),
+                "missing `#[mutate(...)]` (use `#[mutate(skip)]` to leave it untuned)",
+            )
+        })?;
+
+    attr.parse_args_with(|input: ParseStream| {
+        let key: Ident = input.parse()?;
+        match key.to_string().as_str() {
+            "range" => {
+                input.parse::<Token![=]>()?;
+                Ok(Kind::Range(parse_bounds(&input.parse()?)?))
+            }
+            "ranges" => {
+                input.parse::<Token![=]>()?;
+                let content;
+                syn::bracketed!(content in input);
+                let ranges = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+                Ok(Kind::Ranges(
+                    ranges.iter().map(parse_bounds).collect::<Result<_, _>>()?,
+                ))
+            }
+            "sub_name" => Ok(Kind::SubName),
+            "skip" => Ok(Kind::Skip),
+            _ => Err(Error::new(key.span(), "unknown mutate attribute")),
+        }
+    })
+}
+
+/// `a..b` か `a..=b` を `min..=max` にする
+fn parse_bounds(expr: &Expr) -> Result<Bounds, Error> {
+    match expr {
+        Expr::Range(ExprRange {
+            from: Some(from),
+            limits,
+            to: Some(to),
+            ..
+        }) => Ok(Bounds {
+            min: quote! { (#from) },
+            max: match limits {
+                RangeLimits::HalfOpen(_) => quote! { ((#to) - 1) },
+                RangeLimits::Closed(_) => quote! { (#to) },
+            },
+        }),
+        _ => Err(Error::new(expr.span(), "expected `a..b` or `a..=b`")),
+    }
+}
@@ -29,10 +29,35 @@ export type Decision = {
   x: number;
   r: number;
 };
+export type DecisionReason =
+  | { kind: 'opening' }
+  | {
+      kind: 'fire';
+      chain: number;
+      score: number;
+      frame_control: number;
+      frame_chain: number;
+    }
+  | { kind: 'build'; eval_score: number }
+  | { kind: 'forced_death' }
+  | { kind: 'random' }
+  | { kind: 'external' }
+  | { kind: 'fallback' }
+  | { kind: 'unknown' };
+export type SearchStatistics = {
+  depth: number;
+  width: number;
+  parallel: number;
+  nodes: number;
+  votes: number;
+  transpositions?: number;
+};
 export type JsonDecision = {
   think_ms: number;
   log_output: string;
   decisions: Decision[];
+  reason?: DecisionReason;
+  statistics?: SearchStatistics | null;
   boards?: Board[];
   scores?: [number, number][]; // [累積点数, 加算された点数]
 };
@@ -40,6 +65,7 @@ export type JsonData1P = {
   date: string;
   score: number;
   visible_tumos: number;
+  seed?: number | null;
   tumos: KumiPuyo[];
   json_decisions: JsonDecision[];
   url: string;
@@ -62,13 +88,18 @@ export type JsonEvent = {
 };
 export type JsonMatch = {
   won_1p: boolean;
+  draw?: boolean; // 同時に死んだか
   tumos: KumiPuyo[];
   json_events: JsonEvent[];
+  json_decisions_1p?: JsonDecision[]; // ツモ順
+  json_decisions_2p?: JsonDecision[];
 };
 export type JsonData2P = {
   date: string;
   win_count_1p: number;
   win_count_2p: number;
+  draw_count?: number;
   visible_tumos: number;
+  seed?: number | null;
   json_matches: JsonMatch[];
 };
@@ -8,17 +8,17 @@ use std::{
 
 use chrono::Utc;
 use clap::Parser;
-use cpu::{
-    bot::{BeamSearchAI, AI},
-    evaluator::Evaluator,
+use cpu::evaluator::Evaluator;
+use ghoti_optimizer::{
+    harness::{calc_score_1p, new_ai_with_evaluator, new_rng, simulate_1p_average},
+    run_dir::{GenerationRecord, RunDir},
+    Mutateable,
 };
-use ghoti_optimizer::Mutateable;
-use logger::{Logger, NullLogger};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use simulator::{haipuyo_detector::TUMO_PATTERN, simulate_1p, simulate_1p::SimulateResult1P};
+use simulator::{haipuyo_detector::TUMO_PATTERN, simulate_1p::SimulateResult1P};
 
-#[derive(Parser)]
+#[derive(Parser, Serialize)]
 #[clap(
     name = "Ghoti GA Optimizer (1P)",
     author = "morioprog",
@@ -54,24 +54,33 @@ struct Opts {
     /// （`visible_tumos` を `depth` と同じにしたなら増やすべき）
     #[clap(long, default_value = "1")]
     parallel: usize,
+
+    /// 乱数のシード（`BeamSearchAI`・初期個体・親の選び方・交叉に使う。指定しなければランダムに決めて、マニフェストに残す）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// 出力先（チェックポイント・マニフェスト・各世代の最良個体・棋譜）
+    #[clap(long, default_value = "optimizer/logs/ga_tuning_1p")]
+    run_dir: String,
+
+    /// `run_dir` のチェックポイントから再開する（指定しない場合、チェックポイントがあればエラー）
+    #[clap(long)]
+    resume: bool,
 }
 
 fn main() -> Result<(), std::io::Error> {
-    let opts = Opts::parse();
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    opts.seed.get_or_insert_with(rand::random);
     assert!(opts.elite_size < opts.population_size);
 
-    let mut population = match std::fs::File::open("optimizer/logs/ga_tuning_1p/pop.json") {
-        Ok(file) => serde_json::from_reader(file).unwrap_or_else(|e| {
-            eprintln!("pop.json contained invalid data: {}", e);
-            new_population(opts.population_size)
-        }),
-        Err(_) => new_population::<Evaluator>(opts.population_size),
-    };
-
-    // とこぷよのスコアを計算
-    let calc_score = |sim_res: &SimulateResult1P| {
-        ((sim_res.score as f64).powf(1.1f64)) as usize / sim_res.json_decisions.len()
+    let run_dir = RunDir::new(&opts.run_dir);
+    let (mut manifest, checkpoint) = run_dir.start(opts.resume, opts.seed, &opts)?;
+    let mut population = match checkpoint {
+        Some(population) => population,
+        None => new_population::<Evaluator>(opts.population_size, opts.seed),
     };
+    create_dir_all(run_dir.path("best"))?;
 
     // マルチスレッドでシミュレーション
     let matchups = Arc::new(Mutex::new((true, VecDeque::new())));
@@ -92,37 +101,18 @@ fn main() -> Result<(), std::io::Error> {
                 }
             };
 
-            let mut logger: Box<dyn Logger> = Box::new(NullLogger::new("", None).unwrap());
-            let ai: Box<dyn AI> = Box::new(BeamSearchAI::new_customize(ai_eval));
-
-            let mut res = 0;
-            let mut best: Option<SimulateResult1P> = None;
-            for i in 0..opts.simulate_count {
-                let simulate_result_1p = simulate_1p(
-                    &mut logger,
-                    &ai,
-                    opts.visible_tumos,
-                    opts.max_tumos,
-                    // FIXME: 序盤数手が同じになってしまう
-                    Some((haipuyo_margin + i) % TUMO_PATTERN),
-                    Some(opts.required_chain_score),
-                )
-                .unwrap();
-
-                let score = calc_score(&simulate_result_1p);
-                res += score;
-
-                if let Some(sim) = best.clone() {
-                    if score > calc_score(&sim) {
-                        best = Some(simulate_result_1p);
-                    }
-                } else {
-                    best = Some(simulate_result_1p);
-                }
-            }
+            let ai = new_ai_with_evaluator(ai_eval, opts.seed);
+            let (res, best) = simulate_1p_average(
+                &ai,
+                opts.visible_tumos,
+                opts.max_tumos,
+                opts.simulate_count,
+                haipuyo_margin,
+                opts.required_chain_score,
+            )
+            .unwrap();
 
-            send.send(Some((ai_index, res / opts.simulate_count, best)))
-                .ok();
+            send.send(Some((ai_index, res, Some(best)))).ok();
         });
     }
 
@@ -177,7 +167,7 @@ fn main() -> Result<(), std::io::Error> {
                 i + 1,
                 population.members[num].short_name(),
                 score,
-                calc_score(&simulate_results[num].clone().unwrap())
+                calc_score_1p(&simulate_results[num].clone().unwrap())
             );
         }
 
@@ -185,6 +175,8 @@ fn main() -> Result<(), std::io::Error> {
             rand::distributions::WeightedIndex::new(results.iter().map(|&(_, v)| v * v + 1))
                 .unwrap();
 
+        // 親の選び方・交叉は、シードと世代番号から決まる乱数で行う
+        let mut rng = new_rng(opts.seed, &[population.generation as u64]);
         let mut new_population = Population {
             generation: population.generation + 1,
             members: vec![],
@@ -193,10 +185,10 @@ fn main() -> Result<(), std::io::Error> {
             new_population.members.push(population.members[i].clone());
         }
         for i in opts.elite_size..population.members.len() {
-            let p1 = thread_rng().sample(&weighted);
+            let p1 = rng.sample(&weighted);
             let mut p2 = p1;
             while p1 == p2 {
-                p2 = thread_rng().sample(&weighted);
+                p2 = rng.sample(&weighted);
             }
             new_population.members[i] = Evaluator::crossover(
                 &population.members[p1],
@@ -206,19 +198,16 @@ fn main() -> Result<(), std::io::Error> {
                     new_population.generation,
                     i - opts.elite_size
                 ),
+                &mut rng,
             );
         }
 
-        // `pop.json` に今の世代のやつを記録
-        serde_json::to_writer(
-            std::fs::File::create("optimizer/logs/ga_tuning_1p/pop.json").unwrap(),
-            &new_population,
-        )?;
+        // チェックポイントに今の世代のやつを記録
+        run_dir.save_checkpoint(&new_population)?;
 
-        match std::fs::File::create(format!(
-            "optimizer/logs/ga_tuning_1p/best/pop_{}.json",
-            population.generation
-        )) {
+        match std::fs::File::create(
+            run_dir.path(format!("best/pop_{}.json", population.generation)),
+        ) {
             Ok(f) => serde_json::to_writer(std::io::BufWriter::new(f), &new_population.members[0])
                 .unwrap_or_else(|e| eprintln!("Error saving best of generation: {}", e)),
             Err(e) => eprintln!("Error saving best of generation: {}", e),
@@ -226,14 +215,11 @@ fn main() -> Result<(), std::io::Error> {
 
         let best_id = results[0].0;
         let best_eval = population.members[best_id].clone();
-        let file_dir = format!(
-            "kifus/simulator_1p/ga_tuning_1p/ga_{}",
-            best_eval.short_name()
-        );
+        let file_dir = run_dir.path(format!("kifus/ga_{}", best_eval.short_name()));
         create_dir_all(&file_dir)?;
 
         let time_text = Utc::now().format("%Y%m%d_%H%M%S_%f");
-        match std::fs::File::create(format!("{}/{}.json", &file_dir, &time_text)) {
+        match std::fs::File::create(file_dir.join(format!("{}.json", &time_text))) {
             Ok(f) => serde_json::to_writer(
                 std::io::BufWriter::new(f),
                 &simulate_results[best_id].as_ref().unwrap(),
@@ -243,21 +229,22 @@ fn main() -> Result<(), std::io::Error> {
         }
 
         let sec = start.elapsed().as_secs();
+        manifest.history.push(GenerationRecord {
+            generation: population.generation,
+            finished_at: Utc::now(),
+            elapsed_secs: sec,
+            best_name: population.members[results[0].0].short_name(),
+            best_score: results[0].1 as i64,
+        });
+        run_dir.save_manifest(&manifest)?;
+
         let min = sec / 60;
         let sec = sec % 60;
         println!("> Elapsed: {:3}m {:>02}s", min, sec);
         println!();
 
         // 手動でこのファイルを作成するまでループする
-        if std::fs::remove_file("optimizer/logs/ga_tuning_1p/end-request").is_ok() {
-            break;
-        }
-        if std::fs::remove_file(format!(
-            "optimizer/logs/ga_tuning_1p/end-request-{}",
-            population.generation
-        ))
-        .is_ok()
-        {
+        if run_dir.take_end_request(population.generation) {
             break;
         }
 
@@ -276,11 +263,12 @@ struct Population<E: Mutateable> {
     members: Vec<E>,
 }
 
-fn new_population<E: Mutateable>(population_size: usize) -> Population<E> {
+fn new_population<E: Mutateable>(population_size: usize, seed: Option<u64>) -> Population<E> {
+    let mut rng = new_rng(seed, &[]);
     let mut members = vec![];
     members.push(E::default());
     for num in 0..(population_size - 1) {
-        members.push(E::generate(format!("Gen 0 #{:>02}", num)));
+        members.push(E::generate(format!("Gen 0 #{:>02}", num), &mut rng));
     }
     Population {
         generation: 0,
@@ -8,17 +8,16 @@ use std::{
 
 use chrono::Utc;
 use clap::Parser;
-use cpu::{
-    bot::{BeamSearchAI, AI},
-    evaluator::Evaluator,
+use ghoti_optimizer::{
+    harness::{new_ai, new_rng, simulate_match_2p},
+    run_dir::{GenerationRecord, RunDir},
+    BeamSearchParameter, Mutateable,
 };
-use ghoti_optimizer::Mutateable;
-use logger::{Logger, NullLogger};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use simulator::{haipuyo_detector::TUMO_PATTERN, simulate_2p, simulate_2p::SimulateResult2P};
+use simulator::{haipuyo_detector::TUMO_PATTERN, simulate_2p::SimulateResult2P};
 
-#[derive(Parser)]
+#[derive(Parser, Serialize)]
 #[clap(
     name = "Ghoti GA Optimizer (2P)",
     author = "morioprog",
@@ -45,19 +44,33 @@ struct Opts {
     /// 何スレッドでシミュレーションするか
     #[clap(long, default_value = "12")]
     parallel: usize,
+
+    /// 乱数のシード（`BeamSearchAI`・初期個体・親の選び方・交叉に使う。指定しなければランダムに決めて、マニフェストに残す）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// 出力先（チェックポイント・マニフェスト・各世代の最良個体・棋譜）
+    #[clap(long, default_value = "optimizer/logs/ga_tuning_2p")]
+    run_dir: String,
+
+    /// `run_dir` のチェックポイントから再開する（指定しない場合、チェックポイントがあればエラー）
+    #[clap(long)]
+    resume: bool,
 }
 
 fn main() -> Result<(), std::io::Error> {
-    let opts = Opts::parse();
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    opts.seed.get_or_insert_with(rand::random);
     assert!(opts.elite_size < opts.population_size);
 
-    let mut population = match std::fs::File::open("optimizer/logs/ga_tuning_2p/pop.json") {
-        Ok(file) => serde_json::from_reader(file).unwrap_or_else(|e| {
-            eprintln!("pop.json contained invalid data: {}", e);
-            new_population(opts.population_size)
-        }),
-        Err(_) => new_population::<Evaluator>(opts.population_size),
+    let run_dir = RunDir::new(&opts.run_dir);
+    let (mut manifest, checkpoint) = run_dir.start(opts.resume, opts.seed, &opts)?;
+    let mut population = match checkpoint {
+        Some(population) => population,
+        None => new_population::<BeamSearchParameter>(opts.population_size, opts.seed),
     };
+    create_dir_all(run_dir.path("best"))?;
 
     // マルチスレッドでシミュレーション
     let matchups = Arc::new(Mutex::new((true, VecDeque::new())));
@@ -66,7 +79,7 @@ fn main() -> Result<(), std::io::Error> {
         let matchups = matchups.clone();
         let send = send.clone();
         std::thread::spawn(move || loop {
-            // (1PのAIのindex, 1PのAIのEvaluator, 2PのAIのindex, 2PのAIのEvaluator, 配ぷよのマージン)
+            // (1PのAIのindex, 1PのAIのパラメータ, 2PのAIのindex, 2PのAIのパラメータ, 配ぷよのマージン)
             let (p1, p1_e, p2, p2_e, haipuyo_margin) = {
                 let (active, ref mut queue) = *matchups.lock().unwrap();
                 if !active {
@@ -78,16 +91,15 @@ fn main() -> Result<(), std::io::Error> {
                 }
             };
 
-            let mut logger: Box<dyn Logger> = Box::new(NullLogger::new("", None).unwrap());
-            let ai_1p: Box<dyn AI> = Box::new(BeamSearchAI::new_customize(p1_e));
-            let ai_2p: Box<dyn AI> = Box::new(BeamSearchAI::new_customize(p2_e));
-            let simulate_result_2p = simulate_2p(
-                &mut logger,
+            let ai_1p = new_ai(p1_e, opts.seed);
+            let ai_2p = new_ai(p2_e, opts.seed.map(|seed| seed.wrapping_add(1)));
+            let simulate_result_2p = simulate_match_2p(
                 &ai_1p,
                 &ai_2p,
                 opts.win_goal,
                 opts.visible_tumos,
-                Some(haipuyo_margin),
+                haipuyo_margin,
+                opts.seed,
             );
 
             send.send(Some((p1, p2, simulate_result_2p))).ok();
@@ -164,6 +176,8 @@ fn main() -> Result<(), std::io::Error> {
             rand::distributions::WeightedIndex::new(results.iter().map(|&(_, v)| v * v + 1))
                 .unwrap();
 
+        // 親の選び方・交叉は、シードと世代番号から決まる乱数で行う
+        let mut rng = new_rng(opts.seed, &[population.generation as u64]);
         let mut new_population = Population {
             generation: population.generation + 1,
             members: vec![],
@@ -172,12 +186,12 @@ fn main() -> Result<(), std::io::Error> {
             new_population.members.push(population.members[i].clone());
         }
         for i in opts.elite_size..population.members.len() {
-            let p1 = thread_rng().sample(&weighted);
+            let p1 = rng.sample(&weighted);
             let mut p2 = p1;
             while p1 == p2 {
-                p2 = thread_rng().sample(&weighted);
+                p2 = rng.sample(&weighted);
             }
-            new_population.members[i] = Evaluator::crossover(
+            new_population.members[i] = BeamSearchParameter::crossover(
                 &population.members[p1],
                 &population.members[p2],
                 format!(
@@ -185,20 +199,16 @@ fn main() -> Result<(), std::io::Error> {
                     new_population.generation,
                     i - opts.elite_size
                 ),
+                &mut rng,
             );
         }
 
-        // `pop.json` に今の世代のやつを記録
-        serde_json::to_writer(
-            std::fs::File::create("optimizer/logs/ga_tuning_2p/pop.json").unwrap(),
-            &new_population,
-        )
-        .unwrap();
-
-        match std::fs::File::create(format!(
-            "optimizer/logs/ga_tuning_2p/best/pop_{}.json",
-            population.generation
-        )) {
+        // チェックポイントに今の世代のやつを記録
+        run_dir.save_checkpoint(&new_population)?;
+
+        match std::fs::File::create(
+            run_dir.path(format!("best/pop_{}.json", population.generation)),
+        ) {
             Ok(f) => serde_json::to_writer(std::io::BufWriter::new(f), &new_population.members[0])
                 .unwrap_or_else(|e| eprintln!("Error saving best of generation: {}", e)),
             Err(e) => eprintln!("Error saving best of generation: {}", e),
@@ -211,16 +221,16 @@ fn main() -> Result<(), std::io::Error> {
         let best_ai_1_eval = population.members[best_ai_1].clone();
         let best_ai_2_eval = population.members[best_ai_2].clone();
 
-        let file_dir = format!(
-            "kifus/simulator_2p/ga_tuning_2p/{}_{}_vs_{}",
+        let file_dir = run_dir.path(format!(
+            "kifus/{}_{}_vs_{}",
             population.generation,
             best_ai_1_eval.short_name(),
             best_ai_2_eval.short_name()
-        );
+        ));
         create_dir_all(&file_dir)?;
 
         let time_text = Utc::now().format("%Y%m%d_%H%M%S_%f");
-        match std::fs::File::create(format!("{}/{}.json", &file_dir, &time_text)) {
+        match std::fs::File::create(file_dir.join(format!("{}.json", &time_text))) {
             Ok(f) => serde_json::to_writer(
                 std::io::BufWriter::new(f),
                 &simulate_results[best_ai_1 * opts.population_size + best_ai_2]
@@ -232,20 +242,23 @@ fn main() -> Result<(), std::io::Error> {
         }
 
         // Baselineと `opts.win_goal` 先してみる
-        if population.members[results[0].0].sub_name.is_some() {
-            let mut logger: Box<dyn Logger> = Box::new(NullLogger::new("", None).unwrap());
-            let ai_strongest: Box<dyn AI> = Box::new(BeamSearchAI::new_customize(
-                population.members[results[0].0].clone(),
-            ));
-            let ai_baseline: Box<dyn AI> =
-                Box::new(BeamSearchAI::new_customize(Evaluator::default()));
-            let simulate_result_with_baseline = simulate_2p(
-                &mut logger,
+        if population.members[results[0].0]
+            .evaluator
+            .sub_name
+            .is_some()
+        {
+            let ai_strongest = new_ai(population.members[results[0].0].clone(), opts.seed);
+            let ai_baseline = new_ai(
+                BeamSearchParameter::default(),
+                opts.seed.map(|seed| seed.wrapping_add(1)),
+            );
+            let simulate_result_with_baseline = simulate_match_2p(
                 &ai_strongest,
                 &ai_baseline,
                 opts.win_goal,
                 opts.visible_tumos,
-                Some(0),
+                0,
+                opts.seed,
             )?;
             println!(
                 "> {:>8} v.s. Baseline => {:3} - {:3}",
@@ -256,21 +269,22 @@ fn main() -> Result<(), std::io::Error> {
         }
 
         let sec = start.elapsed().as_secs();
+        manifest.history.push(GenerationRecord {
+            generation: population.generation,
+            finished_at: Utc::now(),
+            elapsed_secs: sec,
+            best_name: population.members[results[0].0].short_name(),
+            best_score: results[0].1 as i64,
+        });
+        run_dir.save_manifest(&manifest)?;
+
         let min = sec / 60;
         let sec = sec % 60;
         println!("> Elapsed: {:3}m {:>02}s", min, sec);
         println!();
 
         // 手動でこのファイルを作成するまでループする
-        if std::fs::remove_file("optimizer/logs/ga_tuning_2p/end-request").is_ok() {
-            break;
-        }
-        if std::fs::remove_file(format!(
-            "optimizer/logs/ga_tuning_2p/end-request-{}",
-            population.generation
-        ))
-        .is_ok()
-        {
+        if run_dir.take_end_request(population.generation) {
             break;
         }
 
@@ -289,11 +303,12 @@ struct Population<E: Mutateable> {
     members: Vec<E>,
 }
 
-fn new_population<E: Mutateable>(population_size: usize) -> Population<E> {
+fn new_population<E: Mutateable>(population_size: usize, seed: Option<u64>) -> Population<E> {
+    let mut rng = new_rng(seed, &[]);
     let mut members = vec![];
     members.push(E::default());
     for num in 0..(population_size - 1) {
-        members.push(E::generate(format!("Gen 0 #{:>02}", num)));
+        members.push(E::generate(format!("Gen 0 #{:>02}", num), &mut rng));
     }
     Population {
         generation: 0,
@@ -0,0 +1,152 @@
+use std::time::Instant;
+
+use chrono::Utc;
+use clap::Parser;
+use cpu::evaluator::Evaluator;
+use ghoti_optimizer::{
+    harness::{new_ai_with_evaluator, new_rng, simulate_1p_average},
+    param_vector::ParamVector,
+    run_dir::{write_json_atomic, GenerationRecord, RunDir},
+    spsa::Spsa,
+};
+use serde::Serialize;
+use simulator::haipuyo_detector::TUMO_PATTERN;
+
+#[derive(Parser, Serialize)]
+#[clap(
+    name = "Ghoti SPSA Optimizer (1P)",
+    author = "morioprog",
+    version = "v0.0.1",
+    about = "SPSA を用いたパラメータチューニング（とこぷよ）"
+)]
+struct Opts {
+    /// 最大手数
+    #[clap(long, default_value = "50")]
+    max_tumos: usize,
+
+    /// AI に何手読みさせるか
+    #[clap(long, default_value = "3")]
+    visible_tumos: usize,
+
+    /// 1 回の更新で、`+` 側と `-` 側それぞれ何回とこぷよさせるか
+    #[clap(long, default_value = "10")]
+    simulate_count: usize,
+
+    /// この得点以上の連鎖が打たれたら終了
+    #[clap(long, default_value = "10000")]
+    required_chain_score: usize,
+
+    /// 更新の大きさ
+    #[clap(long, default_value = "0.05")]
+    a: f64,
+
+    /// パラメータをずらす大きさ（今の値に対する比）
+    #[clap(long, default_value = "0.1")]
+    c: f64,
+
+    /// 序盤に更新しすぎないための定数
+    #[clap(long, default_value = "20")]
+    stability: f64,
+
+    /// `BeamSearchAI` とずらす向きの乱数のシード（指定しなければランダムに決めて、マニフェストに残す）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// 出力先（チェックポイント・マニフェスト・今のパラメータ）
+    #[clap(long, default_value = "optimizer/logs/spsa_tuning_1p")]
+    run_dir: String,
+
+    /// `run_dir` のチェックポイントから再開する（指定しない場合、チェックポイントがあればエラー）
+    #[clap(long)]
+    resume: bool,
+}
+
+fn main() -> Result<(), std::io::Error> {
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    opts.seed.get_or_insert_with(rand::random);
+
+    let base = Evaluator::default();
+    let mut params = ParamVector::from_evaluator(&base);
+
+    let run_dir = RunDir::new(&opts.run_dir);
+    let (mut manifest, checkpoint) = run_dir.start(opts.resume, opts.seed, &opts)?;
+    // `Evaluator` の各パラメータの範囲からはみ出さないようにする
+    let mut spsa = match checkpoint {
+        Some(spsa) => spsa,
+        None => Spsa::new(&params.values, opts.a, opts.c, opts.stability),
+    }
+    .with_bounds(params.ranges());
+
+    loop {
+        let start = Instant::now();
+
+        // `--resume` しても同じ向きにずらすように、反復ごとに乱数を作る
+        let mut rng = new_rng(opts.seed, &[spsa.iteration as u64]);
+        let delta = spsa.perturbation(&mut rng);
+        let (plus, minus) = spsa.perturbed(&delta);
+        let new_evaluator = |values: Vec<i32>| {
+            let params = ParamVector {
+                names: params.names.clone(),
+                values,
+            };
+            params.to_evaluator(&base)
+        };
+        let (evaluator_plus, evaluator_minus) = (new_evaluator(plus), new_evaluator(minus));
+
+        // 同じ配ぷよで比べる
+        let haipuyo_margin = (spsa.iteration * opts.simulate_count) % TUMO_PATTERN;
+        let simulate = |evaluator: Evaluator| {
+            simulate_1p_average(
+                &new_ai_with_evaluator(evaluator, opts.seed),
+                opts.visible_tumos,
+                opts.max_tumos,
+                opts.simulate_count,
+                haipuyo_margin,
+                opts.required_chain_score,
+            )
+            .unwrap()
+            .0
+        };
+        let (score_plus, score_minus) = std::thread::scope(|scope| {
+            let plus = scope.spawn(|| simulate(evaluator_plus));
+            let minus = simulate(evaluator_minus);
+            (plus.join().unwrap(), minus)
+        });
+
+        let signal =
+            (score_plus as f64 - score_minus as f64) / ((score_plus + score_minus) as f64).max(1.0);
+        spsa.update(&delta, signal);
+
+        println!(
+            "> Iter {} (haipuyo-margin: {}): + {:>5} / - {:>5}",
+            spsa.iteration, haipuyo_margin, score_plus, score_minus
+        );
+
+        // 今のパラメータを記録
+        params.values = spsa.current();
+        let mut evaluator = params.to_evaluator(&base);
+        evaluator.sub_name = Some(format!("SPSA #{}", spsa.iteration));
+        run_dir.save_checkpoint(&spsa)?;
+        write_json_atomic(&run_dir.path("evaluator.json"), &evaluator)?;
+
+        let sec = start.elapsed().as_secs();
+        manifest.history.push(GenerationRecord {
+            generation: spsa.iteration,
+            finished_at: Utc::now(),
+            elapsed_secs: sec,
+            best_name: evaluator.short_name(),
+            best_score: score_plus.max(score_minus) as i64,
+        });
+        run_dir.save_manifest(&manifest)?;
+
+        println!("> Elapsed: {:3}m {:>02}s", sec / 60, sec % 60);
+
+        // 手動でこのファイルを作成するまでループする
+        if run_dir.take_end_request(spsa.iteration) {
+            break;
+        }
+    }
+
+    Ok(())
+}
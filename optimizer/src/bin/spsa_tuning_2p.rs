@@ -0,0 +1,200 @@
+use std::time::Instant;
+
+use chrono::Utc;
+use clap::Parser;
+use cpu::{bot::AI, evaluator::Evaluator};
+use ghoti_optimizer::{
+    harness::{new_ai_with_evaluator, new_rng, simulate_match_2p},
+    param_vector::ParamVector,
+    run_dir::{write_json_atomic, GenerationRecord, RunDir},
+    spsa::Spsa,
+};
+use serde::Serialize;
+use simulator::haipuyo_detector::TUMO_PATTERN;
+
+#[derive(Parser, Serialize)]
+#[clap(
+    name = "Ghoti SPSA Optimizer (2P)",
+    author = "morioprog",
+    version = "v0.0.1",
+    about = "SPSA を用いたパラメータチューニング（2人対戦）"
+)]
+struct Opts {
+    /// 1 回の更新で、`+` 側と `-` 側を何本先取で対戦させるか（を `parallel` 回）
+    #[clap(long, default_value = "5")]
+    win_goal: usize,
+
+    /// AI に何手読みさせるか
+    #[clap(long, default_value = "10")]
+    visible_tumos: usize,
+
+    /// 1 回の更新で何回対戦させるか（並列に行う。半分は 1P と 2P を入れ替える）
+    #[clap(long, default_value = "4")]
+    parallel: usize,
+
+    /// 更新の大きさ
+    #[clap(long, default_value = "0.05")]
+    a: f64,
+
+    /// パラメータをずらす大きさ（今の値に対する比）
+    #[clap(long, default_value = "0.1")]
+    c: f64,
+
+    /// 序盤に更新しすぎないための定数
+    #[clap(long, default_value = "20")]
+    stability: f64,
+
+    /// 何回更新するごとに、Baseline と `win_goal` 先するか（0 ならしない）
+    #[clap(long, default_value = "10")]
+    baseline_interval: usize,
+
+    /// `BeamSearchAI` とずらす向きの乱数のシード（指定しなければランダムに決めて、マニフェストに残す）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// 出力先（チェックポイント・マニフェスト・今のパラメータ）
+    #[clap(long, default_value = "optimizer/logs/spsa_tuning_2p")]
+    run_dir: String,
+
+    /// `run_dir` のチェックポイントから再開する（指定しない場合、チェックポイントがあればエラー）
+    #[clap(long)]
+    resume: bool,
+}
+
+fn main() -> Result<(), std::io::Error> {
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    opts.seed.get_or_insert_with(rand::random);
+
+    let base = Evaluator::default();
+    let mut params = ParamVector::from_evaluator(&base);
+
+    let run_dir = RunDir::new(&opts.run_dir);
+    let (mut manifest, checkpoint) = run_dir.start(opts.resume, opts.seed, &opts)?;
+    // `Evaluator` の各パラメータの範囲からはみ出さないようにする
+    let mut spsa = match checkpoint {
+        Some(spsa) => spsa,
+        None => Spsa::new(&params.values, opts.a, opts.c, opts.stability),
+    }
+    .with_bounds(params.ranges());
+
+    let new_evaluator = |values: Vec<i32>| {
+        let params = ParamVector {
+            names: params.names.clone(),
+            values,
+        };
+        params.to_evaluator(&base)
+    };
+
+    loop {
+        let start = Instant::now();
+
+        // `--resume` しても同じ向きにずらすように、反復ごとに乱数を作る
+        let mut rng = new_rng(opts.seed, &[spsa.iteration as u64]);
+        let delta = spsa.perturbation(&mut rng);
+        let (plus, minus) = spsa.perturbed(&delta);
+        let (evaluator_plus, evaluator_minus) = (new_evaluator(plus), new_evaluator(minus));
+
+        // (`+` 側の勝ち数, `-` 側の勝ち数)
+        let haipuyo_margin = (spsa.iteration * 200) % TUMO_PATTERN;
+        let (wins_plus, wins_minus) = std::thread::scope(|scope| {
+            let handles = (0..opts.parallel)
+                .map(|i| {
+                    let (evaluator_plus, evaluator_minus) =
+                        (evaluator_plus.clone(), evaluator_minus.clone());
+                    let opts = &opts;
+                    scope.spawn(move || {
+                        let ai_plus = new_ai_with_evaluator(evaluator_plus, opts.seed);
+                        let ai_minus = new_ai_with_evaluator(
+                            evaluator_minus,
+                            opts.seed.map(|seed| seed.wrapping_add(1)),
+                        );
+                        let swap = i % 2 == 1;
+                        let (ai_1p, ai_2p): (&Box<dyn AI>, &Box<dyn AI>) = if swap {
+                            (&ai_minus, &ai_plus)
+                        } else {
+                            (&ai_plus, &ai_minus)
+                        };
+                        let result = simulate_match_2p(
+                            ai_1p,
+                            ai_2p,
+                            opts.win_goal,
+                            opts.visible_tumos,
+                            // 入れ替えた 2 回は同じ配ぷよを使う
+                            (haipuyo_margin + (i / 2) * 20) % TUMO_PATTERN,
+                            opts.seed,
+                        )
+                        .unwrap();
+                        if swap {
+                            (result.win_count_2p, result.win_count_1p)
+                        } else {
+                            (result.win_count_1p, result.win_count_2p)
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .fold((0, 0), |(p, m), (wp, wm)| (p + wp, m + wm))
+        });
+
+        let signal =
+            (wins_plus as f64 - wins_minus as f64) / ((wins_plus + wins_minus) as f64).max(1.0);
+        spsa.update(&delta, signal);
+
+        println!(
+            "> Iter {} (haipuyo-margin: {}): + {:>3} - {:>3} -",
+            spsa.iteration, haipuyo_margin, wins_plus, wins_minus
+        );
+
+        // 今のパラメータを記録
+        params.values = spsa.current();
+        let mut evaluator = params.to_evaluator(&base);
+        evaluator.sub_name = Some(format!("SPSA #{}", spsa.iteration));
+        run_dir.save_checkpoint(&spsa)?;
+        write_json_atomic(&run_dir.path("evaluator.json"), &evaluator)?;
+
+        // Baselineと `opts.win_goal` 先してみる
+        if opts.baseline_interval > 0 && spsa.iteration % opts.baseline_interval == 0 {
+            let ai_current = new_ai_with_evaluator(evaluator.clone(), opts.seed);
+            let ai_baseline = new_ai_with_evaluator(
+                Evaluator::default(),
+                opts.seed.map(|seed| seed.wrapping_add(1)),
+            );
+            let simulate_result_with_baseline = simulate_match_2p(
+                &ai_current,
+                &ai_baseline,
+                opts.win_goal,
+                opts.visible_tumos,
+                0,
+                opts.seed,
+            )?;
+            println!(
+                "> {:>8} v.s. Baseline => {:3} - {:3}",
+                evaluator.short_name(),
+                simulate_result_with_baseline.win_count_1p,
+                simulate_result_with_baseline.win_count_2p
+            );
+        }
+
+        let sec = start.elapsed().as_secs();
+        manifest.history.push(GenerationRecord {
+            generation: spsa.iteration,
+            finished_at: Utc::now(),
+            elapsed_secs: sec,
+            best_name: evaluator.short_name(),
+            best_score: wins_plus.max(wins_minus) as i64,
+        });
+        run_dir.save_manifest(&manifest)?;
+
+        println!("> Elapsed: {:3}m {:>02}s", sec / 60, sec % 60);
+
+        // 手動でこのファイルを作成するまでループする
+        if run_dir.take_end_request(spsa.iteration) {
+            break;
+        }
+    }
+
+    Ok(())
+}
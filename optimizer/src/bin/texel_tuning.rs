@@ -0,0 +1,136 @@
+use std::{
+    fs::{create_dir_all, read_dir, File},
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use cpu::evaluator::Evaluator;
+use ghoti_optimizer::{
+    param_vector::ParamVector,
+    texel::{
+        fit_k, gradient, loss, samples_from_match, trainable_params, Adam, UNTRAINABLE_PARAMETERS,
+    },
+};
+use simulator::simulate_2p::SimulateResult2P;
+
+#[derive(Parser)]
+#[clap(
+    name = "Ghoti Texel Tuner",
+    author = "morioprog",
+    version = "v0.0.1",
+    about = "対戦の棋譜から、勝敗を予測するように評価関数の重みを調整する"
+)]
+struct Opts {
+    /// `SimulateResult2P` の JSON があるディレクトリ（サブディレクトリも含む）
+    #[clap(long, default_value = "kifus/simulator_2p")]
+    kifus: String,
+
+    /// 初期値にする評価関数のパラメータ（JSON）。指定しなければ既定の値を使う
+    #[clap(long)]
+    evaluator: Option<String>,
+
+    /// この手数未満の局面は使わない（序盤は勝敗とほぼ関係ないので）
+    #[clap(long, default_value = "0")]
+    min_tumo_index: usize,
+
+    /// 反復回数
+    #[clap(long, default_value = "1000")]
+    iterations: usize,
+
+    /// 学習率（1 反復あたりに重みが動く大きさの目安）
+    #[clap(long, default_value = "1.0")]
+    learning_rate: f64,
+
+    /// 出力先（`BeamSearchAI::new_customize` に渡せる `Evaluator` の JSON）
+    #[clap(long, default_value = "optimizer/logs/texel_tuning/evaluator.json")]
+    output: String,
+}
+
+fn main() -> Result<(), std::io::Error> {
+    let opts = Opts::parse();
+
+    let evaluator = match &opts.evaluator {
+        Some(path) => {
+            serde_json::from_reader(File::open(path)?).expect("Failed to parse the evaluator")
+        }
+        None => Evaluator::default(),
+    };
+    // 棋譜からは学習できないものは、初期値のまま残す
+    let mut params = trainable_params(&ParamVector::from_evaluator(&evaluator));
+    println!(
+        "Not tuned (cannot be learned from kifus): {}",
+        UNTRAINABLE_PARAMETERS.join(", ")
+    );
+
+    let mut paths = vec![];
+    collect_json_files(Path::new(&opts.kifus), &mut paths)?;
+
+    let mut samples = vec![];
+    for path in &paths {
+        let simulate_result: SimulateResult2P = match serde_json::from_reader(File::open(path)?) {
+            Ok(simulate_result) => simulate_result,
+            Err(e) => {
+                eprintln!("Skipped {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for (i, json_match) in simulate_result.json_matches.iter().enumerate() {
+            match samples_from_match(json_match, &evaluator, &params, opts.min_tumo_index) {
+                Ok(match_samples) => samples.extend(match_samples),
+                Err(e) => eprintln!("Skipped match #{} in {}: {}", i, path.display(), e),
+            }
+        }
+    }
+    assert!(!samples.is_empty(), "No positions found in {}", opts.kifus);
+    println!("{} positions from {} files", samples.len(), paths.len());
+
+    let mut weights = params
+        .values
+        .iter()
+        .map(|&value| value as f64)
+        .collect::<Vec<f64>>();
+    let k = fit_k(&samples, &weights);
+    println!("k = {:e}, loss = {:.6}", k, loss(&samples, &weights, k));
+
+    let mut adam = Adam::new(weights.len(), opts.learning_rate);
+    for iteration in 1..=opts.iterations {
+        let gradient = gradient(&samples, &weights, k);
+        adam.step(&mut weights, &gradient);
+        if iteration % 100 == 0 || iteration == opts.iterations {
+            println!(
+                "iteration {}: loss = {:.6}",
+                iteration,
+                loss(&samples, &weights, k)
+            );
+        }
+    }
+
+    params.values = weights
+        .iter()
+        .map(|&weight| weight.round() as i32)
+        .collect();
+    let mut tuned = params.to_evaluator(&evaluator);
+    tuned.sub_name = Some("Texel".into());
+
+    if let Some(parent) = Path::new(&opts.output).parent() {
+        create_dir_all(parent)?;
+    }
+    serde_json::to_writer_pretty(File::create(&opts.output)?, &tuned)?;
+    println!("Saved to {}", opts.output);
+
+    Ok(())
+}
+
+/// `dir` 以下の JSON ファイルを集める
+fn collect_json_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_json_files(&path, paths)?;
+        } else if path.extension().map_or(false, |ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! 各チューニングで共通の、AI の作成とシミュレーション
+
+use cpu::{
+    bot::{derive_seed, BeamSearchAI, DefaultFireStrategy, AI},
+    evaluator::Evaluator,
+};
+use logger::{Logger, NullLogger};
+use puyoai::es_frame::FrameModel;
+use rand::{rngs::StdRng, SeedableRng};
+use simulator::{
+    haipuyo_detector::TUMO_PATTERN, simulate_1p, simulate_1p::SimulateResult1P, simulate_2p,
+    simulate_2p::SimulateResult2P,
+};
+
+use crate::BeamSearchParameter;
+
+/// シードと `values`（世代番号など）から決まる乱数（シードがなければ毎回ランダム）
+/// （`--resume` しても、同じ世代では同じ乱数になる）
+pub fn new_rng(seed: Option<u64>, values: &[u64]) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(derive_seed(seed, values)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// 評価関数だけを差し替えた `BeamSearchAI`
+pub fn new_ai_with_evaluator(evaluator: Evaluator, seed: Option<u64>) -> Box<dyn AI> {
+    let ai = BeamSearchAI::new_customize(evaluator);
+    match seed {
+        Some(seed) => Box::new(ai.with_seed(seed)),
+        None => Box::new(ai),
+    }
+}
+
+/// 評価関数と発火条件を差し替えた `BeamSearchAI`
+pub fn new_ai(parameter: BeamSearchParameter, seed: Option<u64>) -> Box<dyn AI> {
+    let ai = BeamSearchAI::new_customize(parameter.evaluator).with_fire_strategy(
+        DefaultFireStrategy::new_customize(parameter.fire_thresholds),
+    );
+    match seed {
+        Some(seed) => Box::new(ai.with_seed(seed)),
+        None => Box::new(ai),
+    }
+}
+
+/// とこぷよのスコア（得点の 1.1 乗を手数で割ったもの）
+pub fn calc_score_1p(sim_res: &SimulateResult1P) -> usize {
+    ((sim_res.score as f64).powf(1.1f64)) as usize / sim_res.json_decisions.len()
+}
+
+/// `haipuyo_margin` 番目の配ぷよから順に `simulate_count` 回とこぷよして、
+/// スコアの平均と一番スコアが高かったものを返す
+pub fn simulate_1p_average(
+    ai: &Box<dyn AI>,
+    visible_tumos: usize,
+    max_tumos: usize,
+    simulate_count: usize,
+    haipuyo_margin: usize,
+    required_chain_score: usize,
+) -> Result<(usize, SimulateResult1P), std::io::Error> {
+    let mut logger: Box<dyn Logger> = Box::new(NullLogger::new("", None)?);
+
+    let mut res = 0;
+    let mut best: Option<(usize, SimulateResult1P)> = None;
+    for i in 0..simulate_count {
+        let simulate_result_1p = simulate_1p(
+            &mut logger,
+            ai,
+            visible_tumos,
+            max_tumos,
+            // FIXME: 序盤数手が同じになってしまう
+            Some((haipuyo_margin + i) % TUMO_PATTERN),
+            Some(required_chain_score),
+            None,
+        )?;
+
+        let score = calc_score_1p(&simulate_result_1p);
+        res += score;
+
+        if best
+            .as_ref()
+            .map_or(true, |(best_score, _)| score > *best_score)
+        {
+            best = Some((score, simulate_result_1p));
+        }
+    }
+
+    Ok((res / simulate_count, best.unwrap().1))
+}
+
+/// `win_goal` 本先取の対戦をする（思考時間は無視する）
+pub fn simulate_match_2p(
+    ai_1p: &Box<dyn AI>,
+    ai_2p: &Box<dyn AI>,
+    win_goal: usize,
+    visible_tumos: usize,
+    haipuyo_margin: usize,
+    seed: Option<u64>,
+) -> Result<SimulateResult2P, std::io::Error> {
+    let mut logger: Box<dyn Logger> = Box::new(NullLogger::new("", None)?);
+    simulate_2p(
+        &mut logger,
+        ai_1p,
+        ai_2p,
+        win_goal,
+        visible_tumos,
+        Some(haipuyo_margin),
+        &FrameModel::default(),
+        None,
+        seed,
+    )
+}
@@ -1,3 +1,8 @@
+pub mod harness;
 pub mod mutate;
+pub mod param_vector;
+pub mod run_dir;
+pub mod spsa;
+pub mod texel;
 
-pub use mutate::Mutateable;
+pub use mutate::{BeamSearchParameter, Mutateable};
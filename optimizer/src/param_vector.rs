@@ -0,0 +1,140 @@
+use cpu::evaluator::Evaluator;
+use ghoti_mutate::Mutateable;
+use serde_json::{Map, Value};
+
+/// `Evaluator` の重みを、名前付きのベクトルとして扱う
+/// - 配列（`top_row`）は `top_row_1` のように要素ごとに分ける
+/// - `sub_name` などの重み以外のものは含まない
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamVector {
+    pub names: Vec<String>,
+    pub values: Vec<i32>,
+}
+
+impl ParamVector {
+    pub fn from_evaluator(evaluator: &Evaluator) -> Self {
+        let mut names = vec![];
+        let mut values = vec![];
+        for (name, value) in to_object(evaluator) {
+            match value {
+                Value::Number(number) => {
+                    names.push(name);
+                    values.push(number.as_i64().unwrap() as i32);
+                }
+                Value::Array(array) => {
+                    for (i, value) in array.into_iter().enumerate() {
+                        names.push(format!("{}_{}", name, i + 1));
+                        values.push(value.as_i64().unwrap() as i32);
+                    }
+                }
+                _ => {}
+            }
+        }
+        ParamVector { names, values }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// 各パラメータの範囲 `(min, max)`（`Evaluator::parameter_bounds()` と名前で対応させる。なければ制限しない）
+    pub fn ranges(&self) -> Vec<(i32, i32)> {
+        let ranges = Evaluator::parameter_bounds();
+        self.names
+            .iter()
+            .map(|name| {
+                ranges
+                    .iter()
+                    .find(|(n, _, _)| n == name)
+                    .map_or((i32::MIN, i32::MAX), |&(_, min, max)| {
+                        (min as i32, max as i32)
+                    })
+            })
+            .collect()
+    }
+
+    /// `base` の重みを、このベクトルの値で置き換えたものを返す（`sub_name` などは `base` のまま）
+    pub fn to_evaluator(&self, base: &Evaluator) -> Evaluator {
+        let mut object = to_object(base);
+        for (name, value) in object.iter_mut() {
+            match value {
+                Value::Number(_) => {
+                    if let Some(index) = self.index_of(name) {
+                        *value = self.values[index].into();
+                    }
+                }
+                Value::Array(array) => {
+                    for (i, value) in array.iter_mut().enumerate() {
+                        if let Some(index) = self.index_of(&format!("{}_{}", name, i + 1)) {
+                            *value = self.values[index].into();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        serde_json::from_value(Value::Object(object)).unwrap()
+    }
+}
+
+fn to_object(evaluator: &Evaluator) -> Map<String, Value> {
+    match serde_json::to_value(evaluator).unwrap() {
+        Value::Object(object) => object,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_param_vector() {
+        let evaluator = Evaluator::default();
+        let mut params = ParamVector::from_evaluator(&evaluator);
+        assert_eq!(params.names.len(), params.len());
+        assert!(params.index_of("sub_name").is_none());
+
+        let index = params.index_of("valley").unwrap();
+        assert_eq!(params.values[index], evaluator.valley);
+        let index = params.index_of("top_row_3").unwrap();
+        assert_eq!(params.values[index], evaluator.top_row[2]);
+
+        // そのまま戻せば同じ
+        assert_eq!(
+            serde_json::to_value(params.to_evaluator(&evaluator)).unwrap(),
+            serde_json::to_value(&evaluator).unwrap()
+        );
+
+        params.values[index] = 12345;
+        let evaluator = params.to_evaluator(&evaluator);
+        assert_eq!(evaluator.top_row[2], 12345);
+    }
+
+    #[test]
+    fn test_default_in_bounds() {
+        // 既定の値が、SPSA や交叉で範囲に押し込まれて変わってしまわないように
+        let params = ParamVector::from_evaluator(&Evaluator::default());
+        let ranges = Evaluator::parameter_bounds();
+        assert_eq!(ranges.len(), params.len());
+        assert!(params
+            .ranges()
+            .iter()
+            .all(|&range| range != (i32::MIN, i32::MAX)));
+        for (name, min, max) in ranges {
+            let value = params.values[params.index_of(&name).unwrap()] as i64;
+            assert!(
+                (min..=max).contains(&value),
+                "{} = {} is out of {}..={}",
+                name,
+                value,
+                min,
+                max
+            );
+        }
+    }
+}
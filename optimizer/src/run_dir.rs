@@ -0,0 +1,205 @@
+//! チューニングの実験（run）ごとの出力先・マニフェスト・チェックポイント
+//! - 1 つの run の出力は全て `--run-dir` 以下に置くので、別の run と混ざらない
+
+use std::{
+    fs::{create_dir_all, rename, File},
+    io::{BufWriter, Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// チェックポイントのファイル名
+const CHECKPOINT: &str = "checkpoint.json";
+/// マニフェストのファイル名
+const MANIFEST: &str = "manifest.json";
+
+pub struct RunDir {
+    root: PathBuf,
+}
+
+impl RunDir {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        RunDir {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// run の中のパス
+    pub fn path<P: AsRef<Path>>(&self, relative: P) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// run を始める（`resume` ならチェックポイントを読む）
+    /// - `options` は解析済みのコマンドライン引数（デフォルト値も埋まったもの）で、そのままマニフェストに残す
+    /// - 新しく始めるのにチェックポイントがある場合は、上書きしないようにエラーにする
+    /// - 再開するのにチェックポイントがない・読めない場合もエラーにする
+    pub fn start<T: DeserializeOwned, O: Serialize>(
+        &self,
+        resume: bool,
+        seed: Option<u64>,
+        options: &O,
+    ) -> Result<(Manifest, Option<T>), Error> {
+        create_dir_all(&self.root)?;
+
+        let checkpoint_path = self.path(CHECKPOINT);
+        let checkpoint = if resume {
+            let file = File::open(&checkpoint_path).map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Failed to open {}: {}", checkpoint_path.display(), e),
+                )
+            })?;
+            let checkpoint = serde_json::from_reader(file).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "{} contained invalid data: {}",
+                        checkpoint_path.display(),
+                        e
+                    ),
+                )
+            })?;
+            Some(checkpoint)
+        } else {
+            if checkpoint_path.exists() {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!(
+                        "{} already exists (pass `--resume` or use another `--run-dir`)",
+                        checkpoint_path.display()
+                    ),
+                ));
+            }
+            None
+        };
+
+        let mut manifest = match (resume, File::open(self.path(MANIFEST))) {
+            (true, Ok(file)) => serde_json::from_reader(file)?,
+            _ => Manifest::default(),
+        };
+        manifest.sessions.push(Session {
+            started_at: Utc::now(),
+            options: serde_json::to_value(options)?,
+            seed,
+            git_revision: git_revision(),
+        });
+        self.save_manifest(&manifest)?;
+
+        Ok((manifest, checkpoint))
+    }
+
+    pub fn save_manifest(&self, manifest: &Manifest) -> Result<(), Error> {
+        write_json_atomic(&self.path(MANIFEST), manifest)
+    }
+
+    pub fn save_checkpoint<T: Serialize>(&self, checkpoint: &T) -> Result<(), Error> {
+        write_json_atomic(&self.path(CHECKPOINT), checkpoint)
+    }
+
+    /// `end-request`（か `end-request-{generation}`）が置かれていたら消して true を返す
+    pub fn take_end_request(&self, generation: usize) -> bool {
+        std::fs::remove_file(self.path("end-request")).is_ok()
+            || std::fs::remove_file(self.path(format!("end-request-{}", generation))).is_ok()
+    }
+}
+
+/// run の情報（`manifest.json`）
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// 起動するたびに追加する（`--resume` で再開した場合は複数になる）
+    pub sessions: Vec<Session>,
+    /// 世代（SPSA では反復）ごとの結果
+    pub history: Vec<GenerationRecord>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub started_at: DateTime<Utc>,
+    /// 解析済みのコマンドライン引数（省略したものもデフォルト値で埋まっている）
+    #[serde(default)]
+    pub options: serde_json::Value,
+    pub seed: Option<u64>,
+    /// `git rev-parse HEAD`（取れなければ `None`）
+    pub git_revision: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenerationRecord {
+    pub generation: usize,
+    pub finished_at: DateTime<Utc>,
+    pub elapsed_secs: u64,
+    /// 一番良かった個体の名前とスコア
+    /// （SPSA では、更新後のパラメータの名前と、`+` 側と `-` 側の良かった方のスコア）
+    pub best_name: String,
+    pub best_score: i64,
+}
+
+/// 一時ファイルに書いてから `rename` する（途中で止まっても壊れたファイルが残らないように）
+pub fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, value)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+    rename(&tmp, path)
+}
+
+fn git_revision() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_and_resume() {
+        let root = std::env::temp_dir().join(format!("ghoti_run_dir_{}", std::process::id()));
+        std::fs::remove_dir_all(&root).ok();
+        let run_dir = RunDir::new(&root);
+
+        let options = serde_json::json!({ "seed": 1, "resume": false });
+
+        // 新しく始める
+        let (manifest, checkpoint) = run_dir
+            .start::<Vec<i32>, _>(false, Some(1), &options)
+            .unwrap();
+        assert!(checkpoint.is_none());
+        assert_eq!(manifest.sessions.len(), 1);
+        assert_eq!(manifest.sessions[0].options, options);
+        // チェックポイントがないので再開できない
+        assert!(run_dir.start::<Vec<i32>, _>(true, None, &options).is_err());
+
+        run_dir.save_checkpoint(&vec![1, 2, 3]).unwrap();
+        // 上書きしない
+        assert!(run_dir.start::<Vec<i32>, _>(false, None, &options).is_err());
+
+        let (manifest, checkpoint) = run_dir.start::<Vec<i32>, _>(true, None, &options).unwrap();
+        assert_eq!(checkpoint, Some(vec![1, 2, 3]));
+        assert_eq!(manifest.sessions.len(), 2);
+
+        // 壊れていたらエラー
+        std::fs::write(run_dir.path(CHECKPOINT), "{").unwrap();
+        assert!(run_dir.start::<Vec<i32>, _>(true, None, &options).is_err());
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,181 @@
+//! SPSA（同時摂動確率近似）による重みの調整
+//! - 全てのパラメータを同時にランダムな向きへずらした 2 つ（`+` 側と `-` 側）を比べるだけで勾配を推定するので、
+//!   GA より少ない試合数で収束する
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// これより小さい値のパラメータも、この大きさを基準にしてずらす
+const MIN_SCALE: f64 = 10.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Spsa {
+    /// パラメータを `scale` で割ったもの
+    x: Vec<f64>,
+    /// パラメータごとの大きさの目安（初期値の絶対値）
+    scale: Vec<f64>,
+    /// 更新の大きさ
+    a: f64,
+    /// ずらす大きさ（`scale` に対する比）
+    c: f64,
+    /// 序盤に更新しすぎないための定数（反復回数の 1 割くらいが目安）
+    stability: f64,
+    /// 今までに何回更新したか
+    pub iteration: usize,
+    /// パラメータごとの範囲 `(min, max)`（空なら制限しない。チェックポイントには含めない）
+    #[serde(skip)]
+    bounds: Vec<(i32, i32)>,
+}
+
+impl Spsa {
+    pub fn new(initial: &[i32], a: f64, c: f64, stability: f64) -> Self {
+        let scale = initial
+            .iter()
+            .map(|&value| (value as f64).abs().max(MIN_SCALE))
+            .collect::<Vec<f64>>();
+        Spsa {
+            x: initial
+                .iter()
+                .zip(&scale)
+                .map(|(&value, scale)| value as f64 / scale)
+                .collect(),
+            scale,
+            a,
+            c,
+            stability,
+            iteration: 0,
+            bounds: vec![],
+        }
+    }
+
+    /// パラメータごとの範囲 `(min, max)` を設定する（`perturbed`・`current` はこの範囲に収まる）
+    pub fn with_bounds(mut self, bounds: Vec<(i32, i32)>) -> Self {
+        assert_eq!(bounds.len(), self.x.len());
+        self.bounds = bounds;
+        self
+    }
+
+    /// `i` 番目のパラメータを範囲に収める
+    fn clamp(&self, i: usize, value: i32) -> i32 {
+        match self.bounds.get(i) {
+            Some(&(min, max)) => value.clamp(min, max),
+            None => value,
+        }
+    }
+
+    fn a_k(&self) -> f64 {
+        self.a / (self.iteration as f64 + 1.0 + self.stability).powf(0.602)
+    }
+
+    fn c_k(&self) -> f64 {
+        self.c / (self.iteration as f64 + 1.0).powf(0.101)
+    }
+
+    /// ずらす向き（各要素が ±1）
+    pub fn perturbation<R: Rng>(&self, rng: &mut R) -> Vec<f64> {
+        (0..self.x.len())
+            .map(|_| if rng.gen::<bool>() { 1.0 } else { -1.0 })
+            .collect()
+    }
+
+    /// `delta` の向きにずらした `(+ 側, - 側)` のパラメータ
+    pub fn perturbed(&self, delta: &[f64]) -> (Vec<i32>, Vec<i32>) {
+        let c_k = self.c_k();
+        let shifted = |sign: f64| {
+            self.x
+                .iter()
+                .zip(delta)
+                .zip(&self.scale)
+                .enumerate()
+                .map(|(i, ((x, d), scale))| {
+                    self.clamp(i, ((x + sign * c_k * d) * scale).round() as i32)
+                })
+                .collect()
+        };
+        (shifted(1.0), shifted(-1.0))
+    }
+
+    /// `signal` := (+ 側の結果) - (- 側の結果) として更新する（大きい方が良い）
+    /// - 結果は `[0, 1]` くらいに正規化しておく（勝率など）
+    pub fn update(&mut self, delta: &[f64], signal: f64) {
+        let a_k = self.a_k();
+        let c_k = self.c_k();
+        for (i, (x, d)) in self.x.iter_mut().zip(delta).enumerate() {
+            *x += a_k * signal / (2.0 * c_k * d);
+            // 範囲の外に出ていくと、`+` 側と `-` 側が同じ値になって動けなくなる
+            if let Some(&(min, max)) = self.bounds.get(i) {
+                *x = x.clamp(min as f64 / self.scale[i], max as f64 / self.scale[i]);
+            }
+        }
+        self.iteration += 1;
+    }
+
+    /// 今のパラメータ
+    pub fn current(&self) -> Vec<i32> {
+        self.x
+            .iter()
+            .zip(&self.scale)
+            .enumerate()
+            .map(|(i, (x, scale))| self.clamp(i, (x * scale).round() as i32))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_spsa() {
+        // (100, -200) が最善
+        let target = [100.0, -200.0];
+        let f = |params: &[i32]| {
+            -params
+                .iter()
+                .zip(&target)
+                .map(|(&p, t)| ((p as f64 - t) / 100.0).powi(2))
+                .sum::<f64>()
+        };
+        let distance = |params: &[i32]| -f(params);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut spsa = Spsa::new(&[0, 0], 0.5, 0.1, 10.0);
+        let initial_distance = distance(&spsa.current());
+        for _ in 0..300 {
+            let delta = spsa.perturbation(&mut rng);
+            let (plus, minus) = spsa.perturbed(&delta);
+            spsa.update(&delta, f(&plus) - f(&minus));
+        }
+        assert_eq!(spsa.iteration, 300);
+        assert!(distance(&spsa.current()) < initial_distance);
+    }
+
+    #[test]
+    fn test_spsa_bounds() {
+        let bounds = vec![(-50, 50), (-20, 30)];
+        let in_bounds = |params: &[i32]| {
+            params
+                .iter()
+                .zip(&bounds)
+                .all(|(p, (min, max))| (min..=max).contains(&p))
+        };
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut spsa = Spsa::new(&[0, 0], 0.5, 10.0, 10.0).with_bounds(bounds.clone());
+        for _ in 0..10 {
+            let delta = spsa.perturbation(&mut rng);
+            let (plus, minus) = spsa.perturbed(&delta);
+            assert!(in_bounds(&plus) && in_bounds(&minus));
+        }
+
+        // 大きく動かしても範囲の端で止まる
+        spsa.update(&[1.0, 1.0], 1e6);
+        assert_eq!(spsa.current(), vec![50, 30]);
+        spsa.update(&[1.0, -1.0], -1e6);
+        assert_eq!(spsa.current(), vec![-50, 30]);
+        spsa.update(&[1.0, 1.0], -1e6);
+        assert_eq!(spsa.current(), vec![-50, -20]);
+    }
+}
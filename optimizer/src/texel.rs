@@ -0,0 +1,280 @@
+//! 対戦の棋譜から、局面の評価値の差で勝敗を予測するように重みを調整する（Texel 法）
+
+use cpu::evaluator::{extract_features, Evaluator};
+use puyoai::{plan::Plan, rensa_result::RensaResult};
+use simulator::{
+    convert::revert_core_field,
+    simulate_2p::{JsonMatch, JsonState},
+};
+
+use crate::param_vector::ParamVector;
+
+/// 棋譜の局面からは学習できないパラメータ（`trainable_params` で除く）
+/// - `parameter_values` は連鎖もフレーム数もない `Plan` を作るので、連鎖・フレーム数に掛かる値は常に 0 になる
+/// - `ideal_height_coef_*` は重み同士の積になっている方なので、`evaluator` の値で固定している
+pub const UNTRAINABLE_PARAMETERS: &[&str] = &[
+    "chain",
+    "chain_sq",
+    "chain_score",
+    "chain_frame",
+    "chigiri",
+    "move_frame",
+    "ideal_height_coef_1",
+    "ideal_height_coef_2",
+    "ideal_height_coef_3",
+    "ideal_height_coef_4",
+];
+
+/// `params` から `UNTRAINABLE_PARAMETERS` を除いたもの（除いたものは `to_evaluator` で元の値のままになる）
+pub fn trainable_params(params: &ParamVector) -> ParamVector {
+    let (names, values) = params
+        .names
+        .iter()
+        .zip(&params.values)
+        .filter(|(name, _)| !UNTRAINABLE_PARAMETERS.contains(&name.as_str()))
+        .map(|(name, &value)| (name.clone(), value))
+        .unzip();
+    ParamVector { names, values }
+}
+
+/// 1 局面分の学習データ
+pub struct Sample {
+    /// 各パラメータに掛かる値の、1P と 2P での差（重みとの内積が評価値の差になる）
+    pub diff: Vec<f64>,
+    /// 1P から見た結果（勝ち: 1, 負け: 0, 引き分け: 0.5）
+    pub result: f64,
+}
+
+/// 盤面から、各パラメータに掛かる値を計算する（死んでいたら `None`、盤面が読めなければ `Err`）
+/// - `ideal_height_diff` の係数などの、重み同士の積になっている部分は `evaluator` の値で固定する
+pub fn parameter_values(
+    json_state: &JsonState,
+    evaluator: &Evaluator,
+    params: &ParamVector,
+) -> Result<Option<Vec<f64>>, String> {
+    let plan = Plan::new(
+        revert_core_field(&json_state.field)?,
+        vec![],
+        RensaResult::new(0, 0, 0, false),
+        0,
+        0,
+        0,
+        0,
+        json_state.ojama_fixed,
+        json_state.ojama_ongoing,
+        0,
+        json_state.has_zenkeshi,
+    );
+    let features = extract_features(&plan);
+    if features.is_dead {
+        return Ok(None);
+    }
+
+    let mut values = vec![0.0; params.len()];
+    for term in evaluator.explain_features(&features).terms {
+        if let Some(index) = params.index_of(term.parameter) {
+            values[index] += term.value as f64;
+        }
+    }
+    Ok(Some(values))
+}
+
+/// 1 試合分の学習データを作る（`tumo_index` が `min_tumo_index` 未満の局面は使わない）
+/// - 読めない盤面があれば `Err` を返す（その試合は使わない）
+pub fn samples_from_match(
+    json_match: &JsonMatch,
+    evaluator: &Evaluator,
+    params: &ParamVector,
+    min_tumo_index: usize,
+) -> Result<Vec<Sample>, String> {
+    let result = if json_match.draw {
+        0.5
+    } else if json_match.won_1p {
+        1.0
+    } else {
+        0.0
+    };
+
+    let mut samples = vec![];
+    for json_event in &json_match.json_events {
+        if json_event.json_state_1p.tumo_index < min_tumo_index
+            || json_event.json_state_2p.tumo_index < min_tumo_index
+        {
+            continue;
+        }
+        let values_1p = parameter_values(&json_event.json_state_1p, evaluator, params)?;
+        let values_2p = parameter_values(&json_event.json_state_2p, evaluator, params)?;
+        if let (Some(values_1p), Some(values_2p)) = (values_1p, values_2p) {
+            samples.push(Sample {
+                diff: values_1p
+                    .iter()
+                    .zip(values_2p.iter())
+                    .map(|(v1, v2)| v1 - v2)
+                    .collect(),
+                result,
+            });
+        }
+    }
+    Ok(samples)
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// 1P が勝つ確率の予測値
+fn predict(sample: &Sample, weights: &[f64], k: f64) -> f64 {
+    let eval_diff: f64 = sample.diff.iter().zip(weights).map(|(d, w)| d * w).sum();
+    sigmoid(k * eval_diff)
+}
+
+/// 交差エントロピーの平均
+pub fn loss(samples: &[Sample], weights: &[f64], k: f64) -> f64 {
+    let eps = 1e-12;
+    samples
+        .iter()
+        .map(|sample| {
+            let p = predict(sample, weights, k).clamp(eps, 1.0 - eps);
+            -(sample.result * p.ln() + (1.0 - sample.result) * (1.0 - p).ln())
+        })
+        .sum::<f64>()
+        / samples.len() as f64
+}
+
+/// `loss` の重みについての勾配
+pub fn gradient(samples: &[Sample], weights: &[f64], k: f64) -> Vec<f64> {
+    let mut gradient = vec![0.0; weights.len()];
+    for sample in samples {
+        let error = predict(sample, weights, k) - sample.result;
+        for (g, d) in gradient.iter_mut().zip(&sample.diff) {
+            *g += error * k * d;
+        }
+    }
+    for g in gradient.iter_mut() {
+        *g /= samples.len() as f64;
+    }
+    gradient
+}
+
+/// 評価値の差を勝率に変換するときの係数 `k` を、今の重みで `loss` が最小になるように決める
+pub fn fit_k(samples: &[Sample], weights: &[f64]) -> f64 {
+    // 1e-8 から 1e-2 まで対数で均等に試す
+    (0..=120)
+        .map(|i| 10f64.powf(-8.0 + i as f64 * 0.05))
+        .min_by(|k1, k2| {
+            loss(samples, weights, *k1)
+                .partial_cmp(&loss(samples, weights, *k2))
+                .unwrap()
+        })
+        .unwrap()
+}
+
+/// Adam（特徴量ごとにスケールがかなり違うので、単純な勾配降下法より安定する）
+pub struct Adam {
+    learning_rate: f64,
+    beta1: f64,
+    beta2: f64,
+    m: Vec<f64>,
+    v: Vec<f64>,
+    t: i32,
+}
+
+impl Adam {
+    pub fn new(len: usize, learning_rate: f64) -> Self {
+        Adam {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            m: vec![0.0; len],
+            v: vec![0.0; len],
+            t: 0,
+        }
+    }
+
+    pub fn step(&mut self, weights: &mut [f64], gradient: &[f64]) {
+        self.t += 1;
+        for i in 0..weights.len() {
+            self.m[i] = self.beta1 * self.m[i] + (1.0 - self.beta1) * gradient[i];
+            self.v[i] = self.beta2 * self.v[i] + (1.0 - self.beta2) * gradient[i] * gradient[i];
+            let m_hat = self.m[i] / (1.0 - self.beta1.powi(self.t));
+            let v_hat = self.v[i] / (1.0 - self.beta2.powi(self.t));
+            weights[i] -= self.learning_rate * m_hat / (v_hat.sqrt() + 1e-8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit() {
+        // 1 つ目の値が大きい方が勝つ
+        let samples = (-10..=10)
+            .filter(|i| *i != 0)
+            .map(|i| Sample {
+                diff: vec![i as f64, 1.0],
+                result: if i > 0 { 1.0 } else { 0.0 },
+            })
+            .collect::<Vec<Sample>>();
+
+        let mut weights = vec![0.0, 0.0];
+        let k = 0.01;
+        let initial_loss = loss(&samples, &weights, k);
+
+        let mut adam = Adam::new(weights.len(), 1.0);
+        for _ in 0..100 {
+            let gradient = gradient(&samples, &weights, k);
+            adam.step(&mut weights, &gradient);
+        }
+        assert!(loss(&samples, &weights, k) < initial_loss);
+        assert!(weights[0] > 0.0);
+    }
+
+    #[test]
+    fn test_parameter_values() {
+        let evaluator = Evaluator::default();
+        let params = ParamVector::from_evaluator(&evaluator);
+        let json_state = JsonState {
+            tumo_index: 0,
+            field: "rr/b/yy////".into(),
+            score: 0,
+            ojama_fixed: 0,
+            ojama_ongoing: 0,
+            current_chain: 0,
+            has_zenkeshi: false,
+        };
+
+        let values = parameter_values(&json_state, &evaluator, &params)
+            .unwrap()
+            .unwrap();
+        assert_eq!(values.len(), params.len());
+        // 3列目の高さ
+        assert_eq!(values[params.index_of("third_column_height").unwrap()], 2.0);
+        // 重み同士の積の方は使わない
+        assert_eq!(values[params.index_of("ideal_height_coef_1").unwrap()], 0.0);
+
+        // 読めない盤面は `Err`
+        let json_state = JsonState {
+            field: "rx/////".into(),
+            ..json_state
+        };
+        assert!(parameter_values(&json_state, &evaluator, &params).is_err());
+    }
+
+    #[test]
+    fn test_trainable_params() {
+        let evaluator = Evaluator::default();
+        let all = ParamVector::from_evaluator(&evaluator);
+        let params = trainable_params(&all);
+        assert_eq!(params.len(), all.len() - UNTRAINABLE_PARAMETERS.len());
+        assert!(params.index_of("chain").is_none());
+        assert!(params.index_of("ideal_height_coef_1").is_none());
+        assert!(params.index_of("third_column_height").is_some());
+
+        // 除いたものは元の値のまま
+        let tuned = params.to_evaluator(&evaluator);
+        assert_eq!(tuned.chain, evaluator.chain);
+        assert_eq!(tuned.ideal_height_coef_1, evaluator.ideal_height_coef_1);
+    }
+}
@@ -1,20 +1,34 @@
 use puyoai_core::{
-    field::BitField,
-    field_bit::FieldBit,
+    field::{self, BitField, PuyoPlainField},
     rensa_result::RensaResult,
+};
+#[cfg(all(target_feature = "avx2", target_feature = "bmi2"))]
+use puyoai_core::{
+    field_bit::FieldBit,
     rensa_tracker::{RensaNonTracker, RensaTracker},
 };
 
-use crate::es_frame;
+use super::EsPlainField;
+use crate::es_frame::{self, FrameModel};
 
+/// NOTE: `RensaTracker` を更新できるのは AVX2 / BMI2 の実装だけなので、
+///       `*_with_tracker` はそれ以外の環境では使えない（黙って更新されないままになるのを防ぐため）
 pub trait EsBitField {
     fn es_simulate(&mut self) -> RensaResult;
+    #[cfg(all(target_feature = "avx2", target_feature = "bmi2"))]
     fn es_simulate_with_tracker<T: RensaTracker>(&mut self, tracker: &mut T) -> RensaResult;
     fn es_simulate_from_middle(&mut self, current_chain: usize) -> RensaResult;
+    fn es_simulate_from_middle_with_frame_model(
+        &mut self,
+        current_chain: usize,
+        frame_model: &FrameModel,
+    ) -> RensaResult;
+    #[cfg(all(target_feature = "avx2", target_feature = "bmi2"))]
     fn es_simulate_from_middle_with_tracker<T: RensaTracker>(
         &mut self,
         tracker: &mut T,
         current_chain: usize,
+        frame_model: &FrameModel,
     ) -> RensaResult;
 }
 
@@ -26,18 +40,27 @@ impl EsBitField for BitField {
     }
 
     fn es_simulate_with_tracker<T: RensaTracker>(&mut self, tracker: &mut T) -> RensaResult {
-        self.es_simulate_from_middle_with_tracker(tracker, 1)
+        self.es_simulate_from_middle_with_tracker(tracker, 1, &es_frame::DEFAULT_FRAME_MODEL)
     }
 
     fn es_simulate_from_middle(&mut self, current_chain: usize) -> RensaResult {
+        self.es_simulate_from_middle_with_frame_model(current_chain, &es_frame::DEFAULT_FRAME_MODEL)
+    }
+
+    fn es_simulate_from_middle_with_frame_model(
+        &mut self,
+        current_chain: usize,
+        frame_model: &FrameModel,
+    ) -> RensaResult {
         let mut tracker = RensaNonTracker::new();
-        self.es_simulate_from_middle_with_tracker(&mut tracker, current_chain)
+        self.es_simulate_from_middle_with_tracker(&mut tracker, current_chain, frame_model)
     }
 
     fn es_simulate_from_middle_with_tracker<T: RensaTracker>(
         &mut self,
         tracker: &mut T,
         current_chain: usize,
+        frame_model: &FrameModel,
     ) -> RensaResult {
         let escaped = self.escape_invisible();
 
@@ -57,7 +80,7 @@ impl EsBitField for BitField {
             score += nth_chain_score;
 
             let max_drops = unsafe { self.drop_after_vanish(erased, tracker) };
-            frames += es_frame::FRAMES_CHAIN[max_drops];
+            frames += frame_model.frames_chain[max_drops];
             if max_drops == 0 {
                 quick = true;
             }
@@ -68,6 +91,51 @@ impl EsBitField for BitField {
     }
 }
 
+/// AVX2 / BMI2 が使えない環境向けの実装（`*_with_tracker` はない）
+#[cfg(not(all(target_feature = "avx2", target_feature = "bmi2")))]
+impl EsBitField for BitField {
+    fn es_simulate(&mut self) -> RensaResult {
+        self.es_simulate_from_middle(1)
+    }
+
+    fn es_simulate_from_middle(&mut self, current_chain: usize) -> RensaResult {
+        self.es_simulate_from_middle_with_frame_model(current_chain, &es_frame::DEFAULT_FRAME_MODEL)
+    }
+
+    fn es_simulate_from_middle_with_frame_model(
+        &mut self,
+        current_chain: usize,
+        frame_model: &FrameModel,
+    ) -> RensaResult {
+        es_simulate_from_middle_portable(self, current_chain, frame_model)
+    }
+}
+
+/// `PlainField` に変換して連鎖をシミュレートする（SIMD を使わない）
+/// - 14段目は `escape_invisible` と同様に連鎖に関与させない
+pub fn es_simulate_from_middle_portable(
+    bf: &mut BitField,
+    current_chain: usize,
+    frame_model: &FrameModel,
+) -> RensaResult {
+    let mut pf = PuyoPlainField::new();
+    for x in 1..=field::WIDTH {
+        for y in 1..=13 {
+            pf.set_color(x, y, bf.color(x, y));
+        }
+    }
+
+    let rensa_result = pf.es_simulate_from_middle_with_frame_model(current_chain, frame_model);
+
+    for x in 1..=field::WIDTH {
+        for y in 1..=13 {
+            bf.set_color(x, y, pf.color(x, y));
+        }
+    }
+
+    rensa_result
+}
+
 #[cfg(all(test, target_feature = "avx2", target_feature = "bmi2"))]
 mod tests_simulation {
     use puyoai_core::field::BitField;
@@ -195,3 +263,194 @@ mod tests_simulation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests_portable {
+    use puyoai_core::field::{BitField, CoreField};
+
+    use super::*;
+    use crate::es_frame;
+
+    #[test]
+    fn test_es_simulate_from_middle_portable() {
+        struct SimulationTestcase {
+            field: BitField,
+            current_chain: usize,
+            chain: usize,
+            score: usize,
+            frame: usize,
+            quick: bool,
+            field_after: CoreField,
+        }
+
+        let simulation_testcases = &[
+            SimulationTestcase {
+                field: BitField::from_str(concat!(".BBBB.")),
+                current_chain: 1,
+                chain: 1,
+                score: 40,
+                frame: es_frame::FRAMES_CHAIN[0],
+                quick: true,
+                field_after: CoreField::new(),
+            },
+            SimulationTestcase {
+                field: BitField::from_str(concat!(
+                    ".RBRB.", // 4
+                    "RBRBR.", // 3
+                    "RBRBR.", // 2
+                    "RBRBRR"  // 1
+                )),
+                current_chain: 2,
+                chain: 6,
+                score: 40 * 8 + 40 * 16 + 40 * 32 + 40 * 64 + 40 * 96,
+                frame: es_frame::FRAMES_CHAIN[3] // 2連鎖目
+                    + es_frame::FRAMES_CHAIN[3]  // 3連鎖目
+                    + es_frame::FRAMES_CHAIN[3]  // 4連鎖目
+                    + es_frame::FRAMES_CHAIN[3]  // 5連鎖目
+                    + es_frame::FRAMES_CHAIN[0], // 6連鎖目
+                quick: true,
+                field_after: CoreField::new(),
+            },
+            SimulationTestcase {
+                field: BitField::from_str(concat!(
+                    ".YGGY.", // 4
+                    "BBBBBB", // 3
+                    "GYBBYG", // 2
+                    "BBBBBB"  // 1
+                )),
+                current_chain: 1,
+                chain: 1,
+                score: 140 * 10,
+                frame: es_frame::FRAMES_CHAIN[3],
+                quick: false,
+                field_after: CoreField::from_str(concat!(
+                    ".Y..Y.", // 2
+                    "GYGGYG"  // 1
+                )),
+            },
+            SimulationTestcase {
+                // 13段目のぷよも落ちてくる
+                field: BitField::from_str(concat!(
+                    "..R...", // 13
+                    "..B...", // 12
+                    "..B...", // 11
+                    "..B...", // 10
+                    "..B...", // 9
+                    "..G...", // 8
+                    "..Y...", // 7
+                    "..G...", // 6
+                    "..Y...", // 5
+                    "..G...", // 4
+                    "..Y...", // 3
+                    "..G...", // 2
+                    "..Y..."  // 1
+                )),
+                current_chain: 1,
+                chain: 1,
+                score: 40,
+                frame: es_frame::FRAMES_CHAIN[4],
+                quick: false,
+                field_after: CoreField::from_str(concat!(
+                    "..R...", // 9
+                    "..G...", // 8
+                    "..Y...", // 7
+                    "..G...", // 6
+                    "..Y...", // 5
+                    "..G...", // 4
+                    "..Y...", // 3
+                    "..G...", // 2
+                    "..Y..."  // 1
+                )),
+            },
+        ];
+
+        for testcase in simulation_testcases {
+            let mut bf = testcase.field.clone();
+            let rensa_result = es_simulate_from_middle_portable(
+                &mut bf,
+                testcase.current_chain,
+                &es_frame::DEFAULT_FRAME_MODEL,
+            );
+            assert_eq!(testcase.chain, rensa_result.chain);
+            assert_eq!(testcase.score, rensa_result.score);
+            assert_eq!(testcase.frame, rensa_result.frame);
+            assert_eq!(testcase.quick, rensa_result.quick);
+            assert_eq!(testcase.field_after, CoreField::from_bit_field(&bf));
+        }
+    }
+
+    /// AVX2 / BMI2 の環境では SIMD の実装と比べる（それ以外の環境でも `EsBitField` 経由の結果と比べる）
+    #[test]
+    fn test_es_simulate_portable_agrees_with_simd() {
+        let fields = &[
+            BitField::from_str(concat!(
+                ".G.BRG", // 13
+                "GBRRYR", // 12
+                "RRYYBY", // 11
+                "RGYRBR", // 10
+                "YGYRBY", // 9
+                "YGBGYR", // 8
+                "GRBGYR", // 7
+                "BRBYBY", // 6
+                "RYYBYY", // 5
+                "BRBYBR", // 4
+                "BGBYRR", // 3
+                "YGBGBG", // 2
+                "RBGBGG"  // 1
+            )),
+            BitField::from_str(concat!(
+                "G.....", // 10
+                "Y.....", // 9
+                "GR...B", // 8
+                "RR...B", // 7
+                "GRR..Y", // 6
+                "GGRY.Y", // 5
+                "YYYBGG", // 4
+                "GRBGYG", // 3
+                "GGRBBY", // 2
+                "RRBYYG"  // 1
+            )),
+            BitField::from_str(concat!(
+                ".GY..G", // 11
+                ".GGYBG", // 10
+                "YGRYYB", // 9
+                "BRGGGB", // 8
+                "BYRRYB", // 7
+                "YYRYGY", // 6
+                "BBBYGG", // 5
+                "YYYGRR", // 4
+                "GRBRYR", // 3
+                "GGRBBB", // 2
+                "RRBYYY"  // 1
+            )),
+            BitField::from_str(concat!(
+                "O.....", // 4
+                "RRROOO", // 3
+                "OOOBBB", // 2
+                "RGYBGY"  // 1
+            )),
+        ];
+
+        for field in fields {
+            for current_chain in 1..=3 {
+                let mut bf_simd = field.clone();
+                let mut bf_portable = field.clone();
+                let rensa_result_simd = bf_simd.es_simulate_from_middle(current_chain);
+                let rensa_result_portable = es_simulate_from_middle_portable(
+                    &mut bf_portable,
+                    current_chain,
+                    &es_frame::DEFAULT_FRAME_MODEL,
+                );
+
+                assert_eq!(rensa_result_simd.chain, rensa_result_portable.chain);
+                assert_eq!(rensa_result_simd.score, rensa_result_portable.score);
+                assert_eq!(rensa_result_simd.frame, rensa_result_portable.frame);
+                assert_eq!(rensa_result_simd.quick, rensa_result_portable.quick);
+                assert_eq!(
+                    CoreField::from_bit_field(&bf_simd),
+                    CoreField::from_bit_field(&bf_portable)
+                );
+            }
+        }
+    }
+}
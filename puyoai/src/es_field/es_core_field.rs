@@ -7,13 +7,29 @@ use puyoai_core::{
 use rand::seq::SliceRandom;
 
 use super::EsBitField;
-use crate::es_frame;
+use crate::es_frame::{self, FrameModel};
 
 pub trait EsCoreField {
     fn es_simulate(&mut self) -> RensaResult;
     fn es_simulate_from_middle(&mut self, current_chain: usize) -> RensaResult;
+    fn es_simulate_from_middle_with_frame_model(
+        &mut self,
+        current_chain: usize,
+        frame_model: &FrameModel,
+    ) -> RensaResult;
     fn es_frames_to_drop_next(&self, decision: &Decision) -> usize;
+    fn es_frames_to_drop_next_with_frame_model(
+        &self,
+        decision: &Decision,
+        frame_model: &FrameModel,
+    ) -> usize;
     fn es_drop_ojama(&mut self, ojama: usize, seed: Option<u8>) -> usize;
+    fn es_drop_ojama_with_frame_model(
+        &mut self,
+        ojama: usize,
+        seed: Option<u8>,
+        frame_model: &FrameModel,
+    ) -> usize;
 }
 
 impl EsCoreField for CoreField {
@@ -26,7 +42,17 @@ impl EsCoreField for CoreField {
     }
 
     fn es_simulate_from_middle(&mut self, current_chain: usize) -> RensaResult {
-        let result = self.field_mut().es_simulate_from_middle(current_chain);
+        self.es_simulate_from_middle_with_frame_model(current_chain, &es_frame::DEFAULT_FRAME_MODEL)
+    }
+
+    fn es_simulate_from_middle_with_frame_model(
+        &mut self,
+        current_chain: usize,
+        frame_model: &FrameModel,
+    ) -> RensaResult {
+        let result = self
+            .field_mut()
+            .es_simulate_from_middle_with_frame_model(current_chain, frame_model);
 
         self.update_height();
 
@@ -34,6 +60,14 @@ impl EsCoreField for CoreField {
     }
 
     fn es_frames_to_drop_next(&self, decision: &Decision) -> usize {
+        self.es_frames_to_drop_next_with_frame_model(decision, &es_frame::DEFAULT_FRAME_MODEL)
+    }
+
+    fn es_frames_to_drop_next_with_frame_model(
+        &self,
+        decision: &Decision,
+        frame_model: &FrameModel,
+    ) -> usize {
         let x1 = decision.axis_x();
         let x2 = decision.child_x();
 
@@ -44,18 +78,18 @@ impl EsCoreField for CoreField {
         let diff_row3 = (3 - x1 as isize).abs() as usize;
 
         // 各マスごとの接地までにかかるフレーム数
-        let mut drop_frames = es_frame::FRAMES_TO_MOVE_HORIZONTALLY[diff_row3]
+        let mut drop_frames = frame_model.frames_to_move_horizontally[diff_row3]
             + if decision.rot() == 0 {
-                es_frame::FRAMES_GROUNDING[h1]
+                frame_model.frames_grounding[h1]
             } else if decision.rot() == 2 {
-                es_frame::FRAMES_GROUNDING[h1 + 1]
+                frame_model.frames_grounding[h1 + 1]
             } else {
                 // 子ぷよを接地して軸ぷよをちぎる場合は、子ぷよ側の高さに準拠
-                es_frame::FRAMES_GROUNDING[h1.max(h2)]
+                frame_model.frames_grounding[h1.max(h2)]
             };
 
         // ちぎりにかかるフレーム数
-        drop_frames += es_frame::FRAMES_CHIGIRI[(h1 as isize - h2 as isize).abs() as usize];
+        drop_frames += frame_model.frames_chigiri[(h1 as isize - h2 as isize).abs() as usize];
 
         // 上部でのちぎりは回転モーション中にちぎりモーションが発生します。
         // 以下のマスに軸ぷよがあるちぎり方をする場合、ちぎりのフレーム数に足してください。
@@ -74,7 +108,7 @@ impl EsCoreField for CoreField {
                 && self.height(2) >= field::HEIGHT
                 && self.height(4) >= field::HEIGHT)
         {
-            drop_frames += es_frame::FRAMES_KABEGOE;
+            drop_frames += frame_model.frames_kabegoe;
         }
 
         drop_frames
@@ -82,6 +116,15 @@ impl EsCoreField for CoreField {
 
     /// 盤面におじゃまを落として、それにかかるフレーム数を返す
     fn es_drop_ojama(&mut self, ojama: usize, seed: Option<u8>) -> usize {
+        self.es_drop_ojama_with_frame_model(ojama, seed, &es_frame::DEFAULT_FRAME_MODEL)
+    }
+
+    fn es_drop_ojama_with_frame_model(
+        &mut self,
+        ojama: usize,
+        seed: Option<u8>,
+        frame_model: &FrameModel,
+    ) -> usize {
         if ojama == 0 {
             return 0;
         }
@@ -90,7 +133,7 @@ impl EsCoreField for CoreField {
         assert!(ojama <= 30);
 
         // おじゃま数による硬直フレーム数
-        let ojama_freeze_frame = es_frame::FRAMES_GROUNDING_OJAMA_QUANTITY[ojama];
+        let ojama_freeze_frame = frame_model.frames_grounding_ojama_quantity[ojama];
         // おじゃまの落下に要するフレーム数
         let mut ojama_drop_frame = 0;
 
@@ -104,7 +147,7 @@ impl EsCoreField for CoreField {
                 let height = self.height(x);
                 ojama_drop_frame = std::cmp::max(
                     ojama_drop_frame,
-                    es_frame::FRAMES_GROUNDING_OJAMA_POSITION[height][x],
+                    frame_model.frames_grounding_ojama_position[height][x],
                 );
             }
         }
@@ -125,7 +168,7 @@ impl EsCoreField for CoreField {
                 let height = self.height(*x);
                 ojama_drop_frame = std::cmp::max(
                     ojama_drop_frame,
-                    es_frame::FRAMES_GROUNDING_OJAMA_POSITION[height][*x],
+                    frame_model.frames_grounding_ojama_position[height][*x],
                 );
             }
         }
@@ -1,14 +1,32 @@
 use puyoai_core::{color::Color, field::plain_field::PlainField, rensa_result::RensaResult};
 
-use crate::es_frame;
+use crate::es_frame::{self, FrameModel};
 
 pub trait EsPlainField {
     fn es_simulate(&mut self) -> RensaResult;
+    fn es_simulate_from_middle(&mut self, current_chain: usize) -> RensaResult;
+    fn es_simulate_from_middle_with_frame_model(
+        &mut self,
+        current_chain: usize,
+        frame_model: &FrameModel,
+    ) -> RensaResult;
 }
 
 impl<C: Color> EsPlainField for PlainField<C> {
     fn es_simulate(&mut self) -> RensaResult {
-        let mut chains = 1;
+        self.es_simulate_from_middle(1)
+    }
+
+    fn es_simulate_from_middle(&mut self, current_chain: usize) -> RensaResult {
+        self.es_simulate_from_middle_with_frame_model(current_chain, &es_frame::DEFAULT_FRAME_MODEL)
+    }
+
+    fn es_simulate_from_middle_with_frame_model(
+        &mut self,
+        current_chain: usize,
+        frame_model: &FrameModel,
+    ) -> RensaResult {
+        let mut chains = current_chain;
         let mut score = 0;
         let mut frames = 0;
         let mut quick = false;
@@ -23,7 +41,7 @@ impl<C: Color> EsPlainField for PlainField<C> {
             score += nth_chain_score;
 
             let max_drops = self.drop();
-            frames += es_frame::FRAMES_CHAIN[max_drops];
+            frames += frame_model.frames_chain[max_drops];
             if max_drops == 0 {
                 quick = true;
                 break;
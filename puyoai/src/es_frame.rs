@@ -1,6 +1,10 @@
 // 以下の記事を参考にしている
 // https://puyo-camp.jp/posts/71019
 
+use std::{borrow::Cow, error::Error, fs::File, io::BufReader, path::Path};
+
+use serde::{Deserialize, Serialize};
+
 /// 1 seconds = 60 frames.
 pub const FPS: usize = 60;
 
@@ -53,3 +57,263 @@ pub const FRAMES_GROUNDING_OJAMA_QUANTITY: &'static [usize] = &[
     0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 5, 6, 8, 9, 11, 12, 13, 14, 15, 16, 18, 19, 20, 21, 21, 22, 23,
     24, 24, 25, 25,
 ];
+
+/// マージンタイム（これ以降、おじゃまぷよのレートが下がっていく）
+pub const FRAMES_MARGIN_TIME: usize = 96 * FPS;
+
+/// マージンタイム経過後、おじゃまぷよのレートが下がる間隔
+pub const FRAMES_MARGIN_INTERVAL: usize = 16 * FPS;
+
+/// おじゃまぷよのレート（マージンタイム経過後、間隔ごとに次の値になる。最後の値が下限）
+/// NOTE: ぷよぷよ通（ぷよぷよeスポーツの通ルール）のマージンタイムの表
+///       （96 秒で 70 -> 52 になり、以降 16 秒ごとに下がって 1 で止まる）
+pub const OJAMA_PUYO_RATES: &'static [usize] = &[70, 52, 34, 25, 16, 12, 8, 6, 4, 3, 2, 1];
+
+/// 全消しボーナス（次の連鎖の 1 連鎖目で送るおじゃまぷよの数。レートに依らず 30 個）
+pub const ZENKESHI_OJAMA: usize = 30;
+
+/// 試合開始から `frame` フレーム経過した時点でのおじゃまぷよのレート
+pub fn ojama_puyo_rate(frame: usize) -> usize {
+    if frame < FRAMES_MARGIN_TIME {
+        return OJAMA_PUYO_RATES[0];
+    }
+    let index = 1 + (frame - FRAMES_MARGIN_TIME) / FRAMES_MARGIN_INTERVAL;
+    OJAMA_PUYO_RATES[index.min(OJAMA_PUYO_RATES.len() - 1)]
+}
+
+/// フレーム数のモデル（作品・機種ごとに差し替えられるようにしている）
+/// - 各フィールドの意味は上記の同名の定数を参照
+/// - 省略したフィールドはデフォルト（上記の定数）になる
+/// - デシリアライズするときに `validate` するので、表が短すぎるものは読み込めない
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "FrameModelDef")]
+pub struct FrameModel {
+    pub frames_to_move_horizontally: Cow<'static, [usize]>,
+    pub frames_grounding: Cow<'static, [usize]>,
+    pub frames_chigiri: Cow<'static, [usize]>,
+    pub frames_kabegoe: usize,
+    pub frames_chain: Cow<'static, [usize]>,
+    pub frames_grounding_ojama_position: Cow<'static, [[usize; 7]]>,
+    pub frames_grounding_ojama_quantity: Cow<'static, [usize]>,
+}
+
+/// 上記の定数から成るモデル
+pub static DEFAULT_FRAME_MODEL: FrameModel = FrameModel {
+    frames_to_move_horizontally: Cow::Borrowed(FRAMES_TO_MOVE_HORIZONTALLY),
+    frames_grounding: Cow::Borrowed(FRAMES_GROUNDING),
+    frames_chigiri: Cow::Borrowed(FRAMES_CHIGIRI),
+    frames_kabegoe: FRAMES_KABEGOE,
+    frames_chain: Cow::Borrowed(FRAMES_CHAIN),
+    frames_grounding_ojama_position: Cow::Borrowed(FRAMES_GROUNDING_OJAMA_POSITION),
+    frames_grounding_ojama_quantity: Cow::Borrowed(FRAMES_GROUNDING_OJAMA_QUANTITY),
+};
+
+impl Default for FrameModel {
+    fn default() -> Self {
+        DEFAULT_FRAME_MODEL.clone()
+    }
+}
+
+/// デシリアライズ用（省略したフィールドをデフォルトで埋めてから `FrameModel` にする）
+#[derive(Deserialize)]
+#[serde(default)]
+struct FrameModelDef {
+    frames_to_move_horizontally: Cow<'static, [usize]>,
+    frames_grounding: Cow<'static, [usize]>,
+    frames_chigiri: Cow<'static, [usize]>,
+    frames_kabegoe: usize,
+    frames_chain: Cow<'static, [usize]>,
+    frames_grounding_ojama_position: Cow<'static, [[usize; 7]]>,
+    frames_grounding_ojama_quantity: Cow<'static, [usize]>,
+}
+
+impl Default for FrameModelDef {
+    fn default() -> Self {
+        FrameModelDef {
+            frames_to_move_horizontally: Cow::Borrowed(FRAMES_TO_MOVE_HORIZONTALLY),
+            frames_grounding: Cow::Borrowed(FRAMES_GROUNDING),
+            frames_chigiri: Cow::Borrowed(FRAMES_CHIGIRI),
+            frames_kabegoe: FRAMES_KABEGOE,
+            frames_chain: Cow::Borrowed(FRAMES_CHAIN),
+            frames_grounding_ojama_position: Cow::Borrowed(FRAMES_GROUNDING_OJAMA_POSITION),
+            frames_grounding_ojama_quantity: Cow::Borrowed(FRAMES_GROUNDING_OJAMA_QUANTITY),
+        }
+    }
+}
+
+impl TryFrom<FrameModelDef> for FrameModel {
+    type Error = String;
+
+    fn try_from(def: FrameModelDef) -> Result<Self, Self::Error> {
+        let frame_model = FrameModel {
+            frames_to_move_horizontally: def.frames_to_move_horizontally,
+            frames_grounding: def.frames_grounding,
+            frames_chigiri: def.frames_chigiri,
+            frames_kabegoe: def.frames_kabegoe,
+            frames_chain: def.frames_chain,
+            frames_grounding_ojama_position: def.frames_grounding_ojama_position,
+            frames_grounding_ojama_quantity: def.frames_grounding_ojama_quantity,
+        };
+        frame_model.validate()?;
+        Ok(frame_model)
+    }
+}
+
+impl FrameModel {
+    /// JSON か TOML（拡張子が `.toml` のもの）から読み込む
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        if path.extension().map_or(false, |ext| ext == "toml") {
+            Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+        } else {
+            let reader = BufReader::new(File::open(path)?);
+            Ok(serde_json::from_reader(reader)?)
+        }
+    }
+
+    /// 各表が、引かれうる index を全て含んでいるか（長さがデフォルト以上か）を確かめる
+    /// （表が短すぎるものは、使うときに out-of-bound になる）
+    /// - `frames_grounding_ojama_position` の各行の幅は、型（`[usize; 7]`）で決まっている
+    pub fn validate(&self) -> Result<(), String> {
+        let tables = [
+            (
+                "frames_to_move_horizontally",
+                self.frames_to_move_horizontally.len(),
+                FRAMES_TO_MOVE_HORIZONTALLY.len(),
+            ),
+            (
+                "frames_grounding",
+                self.frames_grounding.len(),
+                FRAMES_GROUNDING.len(),
+            ),
+            (
+                "frames_chigiri",
+                self.frames_chigiri.len(),
+                FRAMES_CHIGIRI.len(),
+            ),
+            ("frames_chain", self.frames_chain.len(), FRAMES_CHAIN.len()),
+            (
+                "frames_grounding_ojama_position",
+                self.frames_grounding_ojama_position.len(),
+                FRAMES_GROUNDING_OJAMA_POSITION.len(),
+            ),
+            (
+                "frames_grounding_ojama_quantity",
+                self.frames_grounding_ojama_quantity.len(),
+                FRAMES_GROUNDING_OJAMA_QUANTITY.len(),
+            ),
+        ];
+        for (name, len, min_len) in tables {
+            if len < min_len {
+                return Err(format!(
+                    "`{}` needs at least {} entries, but has {}",
+                    name, min_len, len
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ojama_puyo_rate() {
+        let expected = [70, 52, 34, 25, 16, 12, 8, 6, 4, 3, 2, 1];
+        assert_eq!(OJAMA_PUYO_RATES, &expected);
+        assert_eq!(ojama_puyo_rate(0), 70);
+
+        // k 段目に下がる境目（1 段目はマージンタイム、以降は 16 秒ごと）
+        for k in 1..expected.len() {
+            let boundary = FRAMES_MARGIN_TIME + FRAMES_MARGIN_INTERVAL * (k - 1);
+            assert_eq!(ojama_puyo_rate(boundary - 1), expected[k - 1], "step {}", k);
+            assert_eq!(ojama_puyo_rate(boundary), expected[k], "step {}", k);
+        }
+
+        // 下限
+        assert_eq!(
+            ojama_puyo_rate(FRAMES_MARGIN_TIME + FRAMES_MARGIN_INTERVAL * 100),
+            1
+        );
+    }
+
+    #[test]
+    fn test_default_frame_model() {
+        let frame_model = FrameModel::default();
+        assert_eq!(frame_model, DEFAULT_FRAME_MODEL);
+        assert_eq!(&frame_model.frames_grounding[..], FRAMES_GROUNDING);
+        assert_eq!(
+            &frame_model.frames_grounding_ojama_position[..],
+            &FRAMES_GROUNDING_OJAMA_POSITION[..]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_frame_model() {
+        let frames_chain = [50, 70, 72, 74, 76, 78, 80, 82, 84, 86, 88, 90, 92];
+
+        // 省略したフィールドはデフォルトになる
+        let frame_model: FrameModel = serde_json::from_str(&format!(
+            r#"{{ "frames_kabegoe": 20, "frames_chain": {:?} }}"#,
+            frames_chain
+        ))
+        .unwrap();
+        assert_eq!(frame_model.frames_kabegoe, 20);
+        assert_eq!(&frame_model.frames_chain[..], &frames_chain);
+        assert_eq!(&frame_model.frames_chigiri[..], FRAMES_CHIGIRI);
+
+        let frame_model: FrameModel = toml::from_str(&format!(
+            "frames_kabegoe = 20\nframes_chain = {:?}\n",
+            frames_chain
+        ))
+        .unwrap();
+        assert_eq!(frame_model.frames_kabegoe, 20);
+        assert_eq!(&frame_model.frames_chain[..], &frames_chain);
+        assert_eq!(&frame_model.frames_chigiri[..], FRAMES_CHIGIRI);
+
+        let serialized = serde_json::to_string(&DEFAULT_FRAME_MODEL).unwrap();
+        let frame_model: FrameModel = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(frame_model, DEFAULT_FRAME_MODEL);
+    }
+
+    #[test]
+    fn test_reject_short_tables() {
+        assert!(DEFAULT_FRAME_MODEL.validate().is_ok());
+
+        // デシリアライズするときに弾かれる
+        let err = serde_json::from_str::<FrameModel>(r#"{ "frames_chain": [50, 70] }"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("frames_chain"), "{}", err);
+
+        // おじゃまの落下位置の表は、各行が 7 列ないといけない
+        let mut rows = FRAMES_GROUNDING_OJAMA_POSITION
+            .iter()
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>();
+        assert!(serde_json::from_str::<FrameModel>(&format!(
+            r#"{{ "frames_grounding_ojama_position": {:?} }}"#,
+            rows
+        ))
+        .is_ok());
+        rows[0].pop();
+        assert!(serde_json::from_str::<FrameModel>(&format!(
+            r#"{{ "frames_grounding_ojama_position": {:?} }}"#,
+            rows
+        ))
+        .is_err());
+
+        // ファイルから読み込むときも弾かれる
+        let path = std::env::temp_dir().join(format!(
+            "ghoti_short_frame_model_{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, "frames_grounding_ojama_quantity = [0, 0, 0]\n").unwrap();
+        let result = FrameModel::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("frames_grounding_ojama_quantity"), "{}", err);
+    }
+}
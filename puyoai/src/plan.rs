@@ -5,7 +5,10 @@ use puyoai_core::{
     rensa_result::RensaResult,
 };
 
-use crate::es_field::EsCoreField;
+use crate::{
+    es_field::EsCoreField,
+    es_frame::{self, FrameModel},
+};
 
 #[derive(Clone)]
 pub struct Plan {
@@ -127,6 +130,7 @@ impl Plan {
         max_depth: usize,
         current_num_chigiri: usize,
         total_frames: usize,
+        frame_model: &FrameModel,
         callback: &mut Callback,
     ) where
         Callback: FnMut(&CoreField, &Vec<Decision>, usize, usize, usize, bool),
@@ -146,7 +150,7 @@ impl Plan {
             }
 
             let is_chigiri = field.is_chigiri_decision(decision);
-            let drop_frames = field.es_frames_to_drop_next(decision);
+            let drop_frames = field.es_frames_to_drop_next_with_frame_model(decision, frame_model);
 
             decisions.push(decision.clone());
 
@@ -180,6 +184,7 @@ impl Plan {
                     max_depth,
                     current_num_chigiri + (is_chigiri as usize),
                     total_frames + drop_frames,
+                    frame_model,
                     callback,
                 );
             }
@@ -195,6 +200,25 @@ impl Plan {
         callback: &mut Callback,
     ) where
         Callback: FnMut(&Plan),
+    {
+        Plan::iterate_available_plans_with_frame_model(
+            field,
+            seq,
+            max_depth,
+            &es_frame::DEFAULT_FRAME_MODEL,
+            callback,
+        );
+    }
+
+    /// フレーム数の見積もりに `frame_model` を使う版
+    pub fn iterate_available_plans_with_frame_model<Callback>(
+        field: &CoreField,
+        seq: &Vec<Kumipuyo>,
+        max_depth: usize,
+        frame_model: &FrameModel,
+        callback: &mut Callback,
+    ) where
+        Callback: FnMut(&Plan),
     {
         // 元の実装では `seq.len() < max_depth` の場合に全配色を試しているが、ここでは省いている
         assert!(seq.len() >= max_depth);
@@ -212,9 +236,10 @@ impl Plan {
 
             if should_fire {
                 let mut cf = field_before_rensa.clone();
-                let rensa_result = cf.es_simulate();
+                let rensa_result = cf.es_simulate_from_middle_with_frame_model(1, frame_model);
                 debug_assert!(rensa_result.chain > 0);
                 if !cf.is_dead() {
+                    let has_zenkeshi = cf.is_zenkeshi();
                     callback(&Plan::new(
                         cf,
                         decisions.clone(),
@@ -226,7 +251,7 @@ impl Plan {
                         0,
                         0,
                         0,
-                        false,
+                        has_zenkeshi,
                     ));
                 }
             } else {
@@ -256,6 +281,7 @@ impl Plan {
             max_depth,
             0,
             0,
+            frame_model,
             &mut f,
         );
     }
@@ -356,6 +382,27 @@ mod tests {
         assert_eq!(plan.frames_to_ignite(), 0);
     }
 
+    #[test]
+    fn test_iterate_available_plans_has_zenkeshi() {
+        let seq = vec![Kumipuyo::new(PuyoColor::RED, PuyoColor::RED)];
+
+        for (field, expected) in [
+            (CoreField::from_str("  RR  "), true),
+            (CoreField::from_str("O RR  "), false),
+        ] {
+            let mut plan_opt: Option<Plan> = None;
+            Plan::iterate_available_plans(&field, &seq, 1, &mut |p: &Plan| {
+                if p.first_decision() == &Decision::new(3, 2) {
+                    plan_opt = Some(p.clone());
+                }
+            });
+
+            let plan = plan_opt.unwrap();
+            assert_eq!(plan.chain(), 1);
+            assert_eq!(plan.has_zenkeshi(), expected);
+        }
+    }
+
     #[test]
     fn test_iterate_available_plans_num_chigiri() {
         let field = CoreField::from_str("  O   ");
@@ -1,5 +1,5 @@
 use clap::Parser;
-use cpu::bot::{BeamSearchAI, RandomAI, AI};
+use cpu::bot::{new_ai, AIContext};
 use ghoti_simulator::simulate_1p;
 use logger::*;
 
@@ -11,7 +11,7 @@ use logger::*;
     about = "とこぷよのシミュレータ"
 )]
 struct Opts {
-    /// AI の名前（`ai.name()`）
+    /// AI の指定（`BeamSearchAI:eval=path.json,depth=30,width=60,parallel=8` のように、名前の後ろに設定を書ける）
     #[clap(long, default_value = "BeamSearchAI")]
     ai: String,
 
@@ -38,16 +38,24 @@ struct Opts {
     /// この得点以上の連鎖が打たれたら終了
     #[clap(long)]
     required_chain_score: Option<usize>,
+
+    /// 乱数のシード（指定しなければランダムに決めて表示する）
+    /// （`BeamSearchAI` の探索を `depth`・`width` で固定すれば、同じシードなら同じ結果になる）
+    #[clap(long)]
+    seed: Option<u64>,
 }
 
 fn main() -> Result<(), std::io::Error> {
-    let opts = Opts::parse();
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    let seed = *opts.seed.get_or_insert_with(rand::random);
+    println!("seed: {}", seed);
 
-    let ais: Vec<Box<dyn AI>> = vec![Box::new(BeamSearchAI::new()), Box::new(RandomAI::new())];
-    let ai = ais
-        .iter()
-        .find(|&ai| ai.name() == opts.ai)
-        .expect(&format!("No AI found: {}", opts.ai));
+    let context = AIContext {
+        seed: opts.seed,
+        ..Default::default()
+    };
+    let ai = new_ai(&opts.ai, &context).unwrap_or_else(|e| panic!("{}", e));
 
     let mut logger: Box<dyn Logger> = match opts.pr_number {
         None => Box::new(FileLogger::new(
@@ -65,11 +73,13 @@ fn main() -> Result<(), std::io::Error> {
 
         let simulate_result_1p = simulate_1p(
             &mut logger,
-            ai,
+            &ai,
             opts.visible_tumos,
             opts.max_tumos,
             opts.haipuyo_margin,
             opts.required_chain_score,
+            // 試行ごとに別の配ぷよにする
+            opts.seed.map(|seed| seed.wrapping_add(trial_index as u64)),
         );
 
         // output JSON file
@@ -1,7 +1,10 @@
+use std::time::Duration;
+
 use clap::Parser;
-use cpu::bot::{BeamSearchAI, RandomAI, AI};
+use cpu::bot::{new_ai, AIContext};
 use ghoti_simulator::simulate_2p;
 use logger::*;
+use puyoai::es_frame::{FrameModel, FPS};
 
 #[derive(Parser)]
 #[clap(
@@ -11,11 +14,11 @@ use logger::*;
     about = "2人対戦のシミュレータ"
 )]
 struct Opts {
-    /// AI の名前（1P）
+    /// AI の指定（1P）（`BeamSearchAI:eval=path.json,depth=30,width=60,parallel=8` のように、名前の後ろに設定を書ける）
     #[clap(long, default_value = "BeamSearchAI")]
     ai_1p: String,
 
-    /// AI の名前（2P）
+    /// AI の指定（2P）
     #[clap(long, default_value = "RandomAI")]
     ai_2p: String,
 
@@ -34,20 +37,85 @@ struct Opts {
     /// 配ぷよ番号
     #[clap(long)]
     haipuyo_margin: Option<usize>,
+
+    /// `ExternalAI` として起動するコマンド（UPI を話すもの。`ExternalAI:command=...` なら AI ごとに指定できる）
+    #[clap(long)]
+    external_ai: Option<String>,
+
+    /// `ExternalAI` の 1 手あたりの制限時間 (ms)
+    #[clap(long, default_value = "5000")]
+    external_ai_timeout: u64,
+
+    /// フレーム数のモデル（JSON / TOML）。指定しなければ既定の値を使う
+    #[clap(long)]
+    frame_model: Option<String>,
+
+    /// 思考時間を実時間どおりにフレーム数として加算する
+    #[clap(long)]
+    realtime: bool,
+
+    /// 思考時間 1 ms あたり何フレーム経過させるか（`--realtime` より優先）
+    #[clap(long)]
+    frames_per_ms: Option<f64>,
+
+    /// 乱数のシード（指定しなければランダムに決めて、棋譜に残す）
+    /// （`BeamSearchAI` の探索を `depth`・`width` で固定すれば、同じシードなら同じ結果になる）
+    /// （思考時間をフレーム数として加算する場合は再現しない）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// 1P の `BeamSearchAI` で、同じ盤面の局面をまとめる（同じビーム幅での強さの比較用）
+    #[clap(long)]
+    transposition_1p: bool,
+
+    /// 2P の `BeamSearchAI` で、同じ盤面の局面をまとめる
+    #[clap(long)]
+    transposition_2p: bool,
 }
 
 fn main() -> Result<(), std::io::Error> {
-    let opts = Opts::parse();
-
-    let ais: Vec<Box<dyn AI>> = vec![Box::new(BeamSearchAI::new()), Box::new(RandomAI::new())];
-    let ai_1p = ais
-        .iter()
-        .find(|&ai| ai.name() == opts.ai_1p)
-        .expect(&format!("No AI found: {}", opts.ai_1p));
-    let ai_2p = ais
-        .iter()
-        .find(|&ai| ai.name() == opts.ai_2p)
-        .expect(&format!("No AI found: {}", opts.ai_2p));
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    let seed = *opts.seed.get_or_insert_with(rand::random);
+    println!("seed: {}", seed);
+
+    let frame_model = match &opts.frame_model {
+        Some(path) => FrameModel::from_file(path).expect("Failed to load the frame model"),
+        None => FrameModel::default(),
+    };
+
+    let frames_per_ms = match opts.frames_per_ms {
+        Some(frames_per_ms) => Some(frames_per_ms),
+        None if opts.realtime => Some(FPS as f64 / 1000.0),
+        None => None,
+    };
+
+    // 同じ AI 同士でも別のシードになるように、1P と 2P で別々に作る
+    let context = AIContext {
+        frame_model: frame_model.clone(),
+        seed: None,
+        transposition: false,
+        external_ai: opts.external_ai.clone(),
+        external_ai_timeout: Duration::from_millis(opts.external_ai_timeout),
+    };
+    let ai_1p = new_ai(
+        &opts.ai_1p,
+        &AIContext {
+            seed: opts.seed,
+            transposition: opts.transposition_1p,
+            ..context.clone()
+        },
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+    let ai_2p = new_ai(
+        &opts.ai_2p,
+        &AIContext {
+            seed: opts.seed.map(|seed| seed.wrapping_add(1)),
+            transposition: opts.transposition_2p,
+            ..context
+        },
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
 
     let mut logger: Box<dyn Logger> = if opts.pr_number.map(|x| x > 0).unwrap_or(false) {
         Box::new(NullLogger::new("", None)?)
@@ -60,11 +128,14 @@ fn main() -> Result<(), std::io::Error> {
 
     let simulate_result_2p = simulate_2p(
         &mut logger,
-        &Box::new(ai_1p),
-        &Box::new(ai_2p),
+        &ai_1p,
+        &ai_2p,
         opts.win_goal,
         opts.visible_tumos,
         opts.haipuyo_margin,
+        &frame_model,
+        frames_per_ms,
+        opts.seed,
     );
 
     // output JSON file
@@ -0,0 +1,78 @@
+use clap::Parser;
+use cpu::{
+    evaluator::Evaluator,
+    upi::pfen::{format_move, parse_pfen_field, parse_pfen_tumos},
+};
+use puyoai::plan::Plan;
+
+#[derive(Parser)]
+#[clap(
+    name = "Ghoti Evaluator Explainer",
+    author = "morioprog",
+    version = "v0.0.1",
+    about = "置ける全ての手について、評価値の内訳を表示する"
+)]
+struct Opts {
+    /// 盤面（pfen 形式。例: `rgyg/rr/b//y/gob/`）
+    field: String,
+
+    /// ツモ（pfen 形式。例: `rb/yy/`）
+    tumos: String,
+
+    /// 何手分置くか（ツモの数まで）
+    #[clap(long, default_value = "1")]
+    depth: usize,
+
+    /// 評価関数のパラメータ（JSON）。指定しなければ既定の値を使う
+    #[clap(long)]
+    evaluator: Option<String>,
+
+    /// 寄与が 0 の項も表示する
+    #[clap(long)]
+    all: bool,
+}
+
+fn main() -> Result<(), std::io::Error> {
+    let opts = Opts::parse();
+
+    let cf = parse_pfen_field(&opts.field).expect("Failed to parse the field");
+    let seq = parse_pfen_tumos(&opts.tumos).expect("Failed to parse the tumos");
+    assert!(
+        opts.depth <= seq.len(),
+        "depth must be at most the number of tumos"
+    );
+
+    let evaluator = match &opts.evaluator {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)
+            .expect("Failed to parse the evaluator"),
+        None => Evaluator::default(),
+    };
+
+    let mut explanations = vec![];
+    Plan::iterate_available_plans(&cf, &seq, opts.depth, &mut |plan: &Plan| {
+        explanations.push((plan.decisions().clone(), evaluator.explain(plan)));
+    });
+    // 評価値が高い順
+    explanations.sort_by(|(_, a), (_, b)| b.score.cmp(&a.score));
+
+    for (decisions, explanation) in explanations {
+        let moves = decisions
+            .iter()
+            .map(format_move)
+            .collect::<Vec<String>>()
+            .join(" ");
+        println!("{} (score: {})", moves, explanation.score);
+        for term in explanation
+            .terms
+            .iter()
+            .filter(|term| opts.all || term.contribution != 0)
+        {
+            println!(
+                "  {:<40} {:>10.3} * {:>6} = {:>8}",
+                term.name, term.value, term.weight, term.contribution
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -71,7 +71,13 @@ fn show_2p_kifu(json_path: &str) -> Result<(), Box<dyn std::error::Error>> {
             let last_json_event = json_match.json_events.iter().last().unwrap();
             format!(
                 "[{}] {:6} - {:6}",
-                if json_match.won_1p { "1P" } else { "2P" },
+                if json_match.draw {
+                    "--"
+                } else if json_match.won_1p {
+                    "1P"
+                } else {
+                    "2P"
+                },
                 last_json_event.json_state_1p.score,
                 last_json_event.json_state_2p.score
             )
@@ -82,20 +88,23 @@ fn show_2p_kifu(json_path: &str) -> Result<(), Box<dyn std::error::Error>> {
 
     let haipuyo = revert_kumipuyo_seq(&json_match.tumos);
     for json_event in &json_match.json_events {
-        show_json_event(&haipuyo, &json_event);
+        show_json_event(&haipuyo, &json_event)?;
     }
 
     Ok(())
 }
 
-fn show_json_event(haipuyo: &Vec<Kumipuyo>, json_event: &JsonEvent) {
+fn show_json_event(
+    haipuyo: &Vec<Kumipuyo>,
+    json_event: &JsonEvent,
+) -> Result<(), Box<dyn std::error::Error>> {
     println!("> {:5} F", json_event.frame);
 
     let json_state_1p = &json_event.json_state_1p;
     let json_state_2p = &json_event.json_state_2p;
 
-    let cf_1p = revert_core_field(&json_state_1p.field);
-    let cf_2p = revert_core_field(&json_state_2p.field);
+    let cf_1p = revert_core_field(&json_state_1p.field)?;
+    let cf_2p = revert_core_field(&json_state_2p.field)?;
 
     let tumo_index_1p = json_state_1p.tumo_index;
     let tumo_index_2p = json_state_2p.tumo_index;
@@ -160,4 +169,5 @@ fn show_json_event(haipuyo: &Vec<Kumipuyo>, json_event: &JsonEvent) {
     );
 
     println!();
+    Ok(())
 }
@@ -0,0 +1,291 @@
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::channel,
+    },
+};
+
+use chrono::Utc;
+use clap::Parser;
+use cpu::bot::{new_ai, AIContext, AISpec, AI};
+use ghoti_simulator::{
+    haipuyo_detector::{HaipuyoDetector, TUMO_PATTERN},
+    simulate_2p::{simulate_match, JsonMatch, SimulateResult2P},
+    sprt::{Sprt, SprtResult},
+};
+use puyoai::es_frame::FrameModel;
+use serde::Serialize;
+
+#[derive(Parser)]
+#[clap(
+    name = "Ghoti SPRT",
+    author = "morioprog",
+    version = "v0.0.1",
+    about = "逐次確率比検定 (SPRT) で、候補の AI が基準の AI より強くなったかを判定する"
+)]
+struct Opts {
+    /// 基準の AI（`BeamSearchAI:eval=path.json,depth=30,width=60` のような指定、または `Evaluator` の JSON ファイル）
+    baseline: String,
+
+    /// 候補の AI（基準の AI と同じ形式）
+    candidate: String,
+
+    /// 帰無仮説の Elo の差
+    #[clap(long, default_value = "0")]
+    elo0: f64,
+
+    /// 対立仮説の Elo の差
+    #[clap(long, default_value = "10")]
+    elo1: f64,
+
+    /// 第一種の過誤の確率
+    #[clap(long, default_value = "0.05")]
+    alpha: f64,
+
+    /// 第二種の過誤の確率
+    #[clap(long, default_value = "0.05")]
+    beta: f64,
+
+    /// 判定できなくても、この組数で打ち切る
+    #[clap(long, default_value = "5000")]
+    max_pairs: usize,
+
+    /// AI に何手読みさせるか
+    #[clap(long, default_value = "2")]
+    visible_tumos: usize,
+
+    /// 最初の組の配ぷよ番号（組ごとに次の番号に進める）
+    #[clap(long, default_value = "0")]
+    haipuyo_margin: usize,
+
+    /// 何スレッドでシミュレーションするか
+    #[clap(long, default_value = "4")]
+    parallel: usize,
+
+    /// `BeamSearchAI` の乱数のシード（基準は `seed`、候補は `seed + 1` を使う。指定しなければランダムに決めて、棋譜に残す）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// フレーム数のモデル（JSON / TOML）。指定しなければ既定の値を使う
+    #[clap(long)]
+    frame_model: Option<String>,
+
+    /// 出力先（負けた試合の棋譜・結果）。指定しなければ `simulator/logs/sprt/<日時>`
+    #[clap(long)]
+    output_dir: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    baseline: String,
+    candidate: String,
+    result: SprtResult,
+    llr: f64,
+    bounds: (f64, f64),
+    score_rate: f64,
+    sprt: Sprt,
+}
+
+/// 候補の AI から見た得点の 2 倍（勝ち 2, 引き分け 1, 負け 0）
+fn candidate_half_points(json_match: &JsonMatch, candidate_is_1p: bool) -> usize {
+    if json_match.draw {
+        1
+    } else if json_match.won_1p == candidate_is_1p {
+        2
+    } else {
+        0
+    }
+}
+
+/// 候補が負けた試合の棋譜を残す
+fn save_lost_kifus(
+    output_dir: &Path,
+    pair: usize,
+    candidate_1p: JsonMatch,
+    candidate_2p: JsonMatch,
+    opts: &Opts,
+) -> Result<(), std::io::Error> {
+    for (json_match, candidate_is_1p, side) in
+        [(candidate_1p, true, "1p"), (candidate_2p, false, "2p")]
+    {
+        if candidate_half_points(&json_match, candidate_is_1p) > 0 {
+            continue;
+        }
+        let (win_count_1p, win_count_2p) = if json_match.won_1p { (1, 0) } else { (0, 1) };
+        let kifu = SimulateResult2P::new(
+            win_count_1p,
+            win_count_2p,
+            0,
+            opts.visible_tumos,
+            opts.seed,
+            vec![json_match],
+        );
+        let file = std::fs::File::create(
+            output_dir.join(format!("lost/pair_{:05}_candidate_{}.json", pair, side)),
+        )?;
+        serde_json::to_writer(std::io::BufWriter::new(file), &kifu)?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), std::io::Error> {
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    let seed = *opts.seed.get_or_insert_with(rand::random);
+    println!("seed: {}", seed);
+
+    let frame_model = match &opts.frame_model {
+        Some(path) => FrameModel::from_file(path).expect("Failed to load the frame model"),
+        None => FrameModel::default(),
+    };
+    let output_dir =
+        PathBuf::from(opts.output_dir.clone().unwrap_or_else(|| {
+            format!("simulator/logs/sprt/{}", Utc::now().format("%Y%m%d_%H%M%S"))
+        }));
+    create_dir_all(output_dir.join("lost"))?;
+
+    let context = AIContext {
+        frame_model: frame_model.clone(),
+        ..Default::default()
+    };
+    let new_ai = |spec: &str, seed: Option<u64>| -> Box<dyn AI> {
+        new_ai(
+            spec,
+            &AIContext {
+                seed,
+                ..context.clone()
+            },
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    };
+    let spec_name = |spec: &String| {
+        spec.parse::<AISpec>()
+            .map(|spec| spec.display_name())
+            .unwrap_or_else(|_| spec.clone())
+    };
+
+    // 先に作れるか確かめておく
+    new_ai(&opts.baseline, None);
+    new_ai(&opts.candidate, None);
+
+    let mut sprt = Sprt::new(opts.elo0, opts.elo1, opts.alpha, opts.beta);
+    let (lower, upper) = sprt.bounds();
+    println!(
+        "{} (candidate) v.s. {} (baseline): elo0 = {}, elo1 = {}, LLR bounds = [{:.2}, {:.2}]",
+        spec_name(&opts.candidate),
+        spec_name(&opts.baseline),
+        opts.elo0,
+        opts.elo1,
+        lower,
+        upper
+    );
+
+    let next_pair = AtomicUsize::new(0);
+    let active = AtomicBool::new(true);
+    let (send, pair_results) = channel();
+
+    // `AI` はスレッド間で共有できないので、各スレッドで作る
+    std::thread::scope(|scope| -> Result<(), std::io::Error> {
+        for _ in 0..opts.parallel.max(1) {
+            let send = send.clone();
+            let (next_pair, active, frame_model, new_ai) =
+                (&next_pair, &active, &frame_model, &new_ai);
+            let opts = &opts;
+            scope.spawn(move || {
+                let baseline = new_ai(&opts.baseline, opts.seed);
+                let candidate = new_ai(&opts.candidate, opts.seed.map(|seed| seed.wrapping_add(1)));
+                while active.load(Ordering::SeqCst) {
+                    let pair = next_pair.fetch_add(1, Ordering::SeqCst);
+                    if pair >= opts.max_pairs {
+                        break;
+                    }
+
+                    // 同じ配ぷよで、先手・後手を入れ替えて 2 試合
+                    let seq = HaipuyoDetector::retrieve_haipuyo(
+                        (opts.haipuyo_margin + pair) % TUMO_PATTERN,
+                    );
+                    let candidate_1p = simulate_match(
+                        &candidate,
+                        &baseline,
+                        &seq,
+                        opts.visible_tumos,
+                        pair,
+                        frame_model,
+                        None,
+                    );
+                    let candidate_2p = simulate_match(
+                        &baseline,
+                        &candidate,
+                        &seq,
+                        opts.visible_tumos,
+                        pair,
+                        frame_model,
+                        None,
+                    );
+                    if send.send((pair, candidate_1p, candidate_2p)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(send);
+
+        for (pair, candidate_1p, candidate_2p) in pair_results.iter() {
+            if sprt.result() != SprtResult::Continue {
+                continue;
+            }
+
+            let half_points = candidate_half_points(&candidate_1p, true)
+                + candidate_half_points(&candidate_2p, false);
+            sprt.add_pair(half_points);
+
+            // 書き込みに失敗したら、他のスレッドを止めてから抜ける
+            if let Err(e) = save_lost_kifus(&output_dir, pair, candidate_1p, candidate_2p, &opts) {
+                active.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+
+            println!(
+                "pairs: {:>5}, score: {:>5.1}%, LLR: {:>+6.2}",
+                sprt.pair_count(),
+                sprt.score_rate() * 100.0,
+                sprt.llr()
+            );
+            if sprt.result() != SprtResult::Continue {
+                active.store(false, Ordering::SeqCst);
+            }
+        }
+        Ok(())
+    })?;
+
+    let result = sprt.result();
+    println!();
+    println!(
+        "{}: {} pairs ({} games), score: {:.1}%, LLR: {:+.2}",
+        match result {
+            SprtResult::AcceptH0 => "H0 accepted (no improvement)",
+            SprtResult::AcceptH1 => "H1 accepted (improvement)",
+            SprtResult::Continue => "Inconclusive",
+        },
+        sprt.pair_count(),
+        sprt.pair_count() * 2,
+        sprt.score_rate() * 100.0,
+        sprt.llr()
+    );
+
+    let summary = Summary {
+        baseline: opts.baseline.clone(),
+        candidate: opts.candidate.clone(),
+        result,
+        llr: sprt.llr(),
+        bounds: sprt.bounds(),
+        score_rate: sprt.score_rate(),
+        sprt,
+    };
+    let file = std::fs::File::create(output_dir.join("summary.json"))?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &summary)?;
+
+    Ok(())
+}
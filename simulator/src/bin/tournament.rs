@@ -0,0 +1,243 @@
+use std::{collections::VecDeque, fs::create_dir_all, path::PathBuf, sync::Mutex};
+
+use chrono::Utc;
+use clap::Parser;
+use cpu::bot::{new_ai, AIContext, AISpec, AI};
+use ghoti_simulator::{
+    rating::{bradley_terry, Crosstable, Rating},
+    simulate_2p,
+    simulate_2p::SimulateResult2P,
+};
+use logger::{Logger, NullLogger};
+use puyoai::es_frame::FrameModel;
+use serde::Serialize;
+
+#[derive(Parser)]
+#[clap(
+    name = "Ghoti Tournament",
+    author = "morioprog",
+    version = "v0.0.1",
+    about = "複数の AI の総当たり戦（Elo レーティングを推定する）"
+)]
+struct Opts {
+    /// 参加する AI（`BeamSearchAI:eval=path.json,depth=30,width=60` のような指定、または `Evaluator` の JSON ファイル）
+    #[clap(required = true, num_args = 2..)]
+    ais: Vec<String>,
+
+    /// 1 組あたり何本先取か
+    #[clap(long, default_value = "30")]
+    win_goal: usize,
+
+    /// AI に何手読みさせるか
+    #[clap(long, default_value = "2")]
+    visible_tumos: usize,
+
+    /// 配ぷよ番号（全ての組で同じ配ぷよを使う）
+    #[clap(long, default_value = "0")]
+    haipuyo_margin: usize,
+
+    /// 何スレッドでシミュレーションするか
+    #[clap(long, default_value = "4")]
+    parallel: usize,
+
+    /// 乱数のシード（i 番目の AI は `seed + i` を使う。指定しなければランダムに決めて、結果に残す）
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// フレーム数のモデル（JSON / TOML）。指定しなければ既定の値を使う
+    #[clap(long)]
+    frame_model: Option<String>,
+
+    /// 出力先（棋譜・結果）。指定しなければ `simulator/logs/tournament/<日時>`
+    #[clap(long)]
+    output_dir: Option<String>,
+}
+
+/// 参加する AI の名前（同じ名前があれば番号を付ける）
+fn participant_names(specs: &[String]) -> Vec<String> {
+    let names: Vec<String> = specs
+        .iter()
+        .map(|spec| {
+            spec.parse::<AISpec>()
+                .map(|spec| spec.display_name())
+                .unwrap_or_else(|_| spec.clone())
+        })
+        .collect();
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            if names.iter().filter(|other| *other == name).count() > 1 {
+                format!("{}#{}", name, i)
+            } else {
+                name.clone()
+            }
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct Summary {
+    win_goal: usize,
+    visible_tumos: usize,
+    haipuyo_margin: usize,
+    seed: Option<u64>,
+    ais: Vec<String>,
+    crosstable: Crosstable,
+    ratings: Vec<Rating>,
+}
+
+fn main() -> Result<(), std::io::Error> {
+    let mut opts = Opts::parse();
+    // 後から同じ乱数を再現できるように、シードは必ず決めておく
+    let seed = *opts.seed.get_or_insert_with(rand::random);
+    println!("seed: {}", seed);
+
+    let frame_model = match &opts.frame_model {
+        Some(path) => FrameModel::from_file(path).expect("Failed to load the frame model"),
+        None => FrameModel::default(),
+    };
+    let names = participant_names(&opts.ais);
+    let output_dir = PathBuf::from(opts.output_dir.clone().unwrap_or_else(|| {
+        format!(
+            "simulator/logs/tournament/{}",
+            Utc::now().format("%Y%m%d_%H%M%S")
+        )
+    }));
+    create_dir_all(&output_dir)?;
+
+    let context = AIContext {
+        frame_model: frame_model.clone(),
+        ..Default::default()
+    };
+    let new_ai = |spec: &str, seed: Option<u64>| -> Box<dyn AI> {
+        new_ai(
+            spec,
+            &AIContext {
+                seed,
+                ..context.clone()
+            },
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    };
+
+    // 先に作れるか確かめておく
+    for spec in &opts.ais {
+        new_ai(spec, None);
+    }
+
+    // 全ての組 (i < j)
+    let mut pairings = VecDeque::new();
+    for i in 0..opts.ais.len() {
+        for j in (i + 1)..opts.ais.len() {
+            pairings.push_back((i, j));
+        }
+    }
+    let pairings = Mutex::new(pairings);
+    let crosstable = Mutex::new(Crosstable::new(names.clone()));
+
+    // `AI` はスレッド間で共有できないので、各スレッドで作る
+    std::thread::scope(|scope| -> Result<(), std::io::Error> {
+        let workers: Vec<_> = (0..opts.parallel.max(1))
+            .map(|_| {
+                scope.spawn(|| -> Result<(), std::io::Error> {
+                    loop {
+                        let (i, j) = match pairings.lock().unwrap().pop_front() {
+                            Some(pairing) => pairing,
+                            None => return Ok(()),
+                        };
+
+                        let seed = |k: usize| opts.seed.map(|seed| seed.wrapping_add(k as u64));
+                        let ai_1p = new_ai(&opts.ais[i], seed(i));
+                        let ai_2p = new_ai(&opts.ais[j], seed(j));
+                        let mut logger: Box<dyn Logger> = Box::new(NullLogger::new("", None)?);
+                        let result: SimulateResult2P = simulate_2p(
+                            &mut logger,
+                            &ai_1p,
+                            &ai_2p,
+                            opts.win_goal,
+                            opts.visible_tumos,
+                            Some(opts.haipuyo_margin),
+                            &frame_model,
+                            None,
+                            opts.seed,
+                        )?;
+
+                        println!(
+                            "{} v.s. {} => {} - {} ({} draws)",
+                            names[i],
+                            names[j],
+                            result.win_count_1p,
+                            result.win_count_2p,
+                            result.draw_count
+                        );
+                        crosstable.lock().unwrap().add(
+                            i,
+                            j,
+                            result.win_count_1p,
+                            result.win_count_2p,
+                            result.draw_count,
+                        );
+
+                        let file = std::fs::File::create(
+                            output_dir.join(format!("{}_vs_{}.json", names[i], names[j])),
+                        )?;
+                        serde_json::to_writer(std::io::BufWriter::new(file), &result)?;
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap()?;
+        }
+        Ok(())
+    })?;
+
+    let crosstable = crosstable.into_inner().unwrap();
+    let mut ratings = bradley_terry(&crosstable);
+
+    // 対戦表（行の AI が列の AI に何勝したか）
+    println!();
+    print!("{:>16}", "");
+    for j in 0..names.len() {
+        print!(" {:>6}", j + 1);
+    }
+    println!();
+    for i in 0..names.len() {
+        print!("{:>2}. {:>12}", i + 1, names[i]);
+        for j in 0..names.len() {
+            if i == j {
+                print!(" {:>6}", "-");
+            } else {
+                print!(" {:>6}", crosstable.wins[i][j]);
+            }
+        }
+        println!();
+    }
+
+    ratings.sort_by(|a, b| b.elo.partial_cmp(&a.elo).unwrap());
+    println!();
+    for (rank, rating) in ratings.iter().enumerate() {
+        println!(
+            "{:>2}. {:>12}: {:>+7.1} ± {:>5.1}",
+            rank + 1,
+            rating.name,
+            rating.elo,
+            rating.elo_error
+        );
+    }
+
+    let summary = Summary {
+        win_goal: opts.win_goal,
+        visible_tumos: opts.visible_tumos,
+        haipuyo_margin: opts.haipuyo_margin,
+        seed: opts.seed,
+        ais: opts.ais.clone(),
+        crosstable,
+        ratings,
+    };
+    let file = std::fs::File::create(output_dir.join("summary.json"))?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &summary)?;
+
+    Ok(())
+}
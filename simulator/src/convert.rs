@@ -1,6 +1,7 @@
+use cpu::upi::pfen::{format_pfen_field, parse_pfen_field};
 use puyoai::{
     color::{Color, PuyoColor},
-    field::{self, CoreField},
+    field::CoreField,
     kumipuyo::Kumipuyo,
 };
 
@@ -32,40 +33,15 @@ pub fn revert_kumipuyo_seq(tumos: &Vec<String>) -> Vec<Kumipuyo> {
 }
 
 /// 盤面を pfen-like な形式に変換
-/// （pfen文字列: https://github.com/TukamotoRyuzo/upi-protocol/blob/master/README.md）
+/// （`cpu::upi::pfen` の実装をそのまま使う）
 pub fn convert_core_field(cf: &CoreField) -> String {
-    let mut ret = "".to_string();
-    for x in 1..=field::WIDTH {
-        for y in 1..=cf.height(x) {
-            ret += match cf.color(x, y) {
-                PuyoColor::RED => "r",
-                PuyoColor::BLUE => "b",
-                PuyoColor::YELLOW => "y",
-                PuyoColor::GREEN => "g",
-                PuyoColor::OJAMA => "o",
-                _ => unreachable!(),
-            };
-        }
-        ret += "/";
-    }
-    ret
+    format_pfen_field(cf)
 }
 
 /// pfen-like な文字列から盤面に変換
-pub fn revert_core_field(pfen: &str) -> CoreField {
-    let columns = pfen.split("/").collect::<Vec<&str>>();
-    debug_assert_eq!(columns.len(), 7);
-
-    let mut cf = CoreField::new();
-    for x in 0..field::WIDTH {
-        let bytes = columns[x].as_bytes();
-        for y in 0..columns[x].len() {
-            let color = PuyoColor::from_byte(bytes[y]);
-            cf.field_mut().set_color(x + 1, y + 1, color);
-        }
-    }
-    cf.update_height();
-    cf
+/// - 以前は `PuyoColor::from_byte` で 1 文字ずつ読んでいたので、大文字の棋譜も読めるようにしておく
+pub fn revert_core_field(pfen: &str) -> Result<CoreField, String> {
+    parse_pfen_field(&pfen.to_ascii_lowercase())
 }
 
 #[cfg(test)]
@@ -150,10 +126,10 @@ mod tests {
 
     #[test]
     fn test_revert_core_field() {
-        assert_eq!(revert_core_field("//////"), CoreField::new());
+        assert_eq!(revert_core_field("//////").unwrap(), CoreField::new());
 
         assert_eq!(
-            revert_core_field("rggyggrgyg/rgrygrr/brby/ybgby/ybyg/gyggyybb/"),
+            revert_core_field("rggyggrgyg/rgrygrr/brby/ybgby/ybyg/gyggyybb/").unwrap(),
             CoreField::from_str(concat!(
                 "G.....", // 10
                 "Y.....", // 9
@@ -176,7 +152,8 @@ mod tests {
                 "bgyybyggrryrb/",
                 "gbrbybyybbbyr/",
                 "ggrryyrryryrg/"
-            )),
+            ))
+            .unwrap(),
             CoreField::from_str(concat!(
                 ".G.BRG", // 13
                 "GBRRYR", // 12
@@ -194,4 +171,36 @@ mod tests {
             )),
         );
     }
+
+    #[test]
+    fn test_revert_old_kifu_field() {
+        let expected = CoreField::from_str(concat!(
+            "O.....", // 13
+            "G.....", // 12
+            "G.....", // 11
+            "G.....", // 10
+            "G.....", // 9
+            "G.....", // 8
+            "G.....", // 7
+            "G.....", // 6
+            "G.....", // 5
+            "G.....", // 4
+            "G.....", // 3
+            "GO....", // 2
+            "RBY..O"  // 1
+        ));
+
+        // 以前の `convert_core_field` が書き出していた形式（13段目・おじゃまぷよを含む）
+        assert_eq!(
+            revert_core_field("rgggggggggggo/bo/y////o/").unwrap(),
+            expected
+        );
+        assert_eq!(
+            revert_core_field("RGGGGGGGGGGGO/BO/Y////O/").unwrap(),
+            expected
+        );
+
+        assert!(revert_core_field("rx/////").is_err());
+        assert!(revert_core_field("r/r/r/r/r/r/r/").is_err());
+    }
 }
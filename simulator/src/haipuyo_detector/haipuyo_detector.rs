@@ -87,7 +87,12 @@ impl HaipuyoDetector {
     }
 
     pub fn random_haipuyo() -> Vec<Kumipuyo> {
-        let key = rand::thread_rng().gen_range(0..TUMO_PATTERN);
+        HaipuyoDetector::random_haipuyo_with_rng(&mut rand::thread_rng())
+    }
+
+    /// 乱数を指定できる版（シードを固定すれば同じ配ぷよになる）
+    pub fn random_haipuyo_with_rng<R: Rng>(rng: &mut R) -> Vec<Kumipuyo> {
+        let key = rng.gen_range(0..TUMO_PATTERN);
 
         HaipuyoDetector::retrieve_haipuyo(key)
     }
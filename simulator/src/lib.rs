@@ -3,6 +3,8 @@ pub mod simulate_2p;
 
 pub mod convert;
 pub mod haipuyo_detector;
+pub mod rating;
+pub mod sprt;
 
 pub use simulate_1p::simulate_1p;
 pub use simulate_2p::simulate_2p;
@@ -0,0 +1,147 @@
+//! 総当たりの結果から、Bradley–Terry モデルでレーティングを推定する
+
+use serde::{Deserialize, Serialize};
+
+/// 総当たりの結果
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Crosstable {
+    pub names: Vec<String>,
+    /// wins[i][j] := i が j に勝った回数
+    pub wins: Vec<Vec<usize>>,
+    /// draws[i][j] := i と j が引き分けた回数（対称）
+    pub draws: Vec<Vec<usize>>,
+}
+
+impl Crosstable {
+    pub fn new(names: Vec<String>) -> Self {
+        let n = names.len();
+        Crosstable {
+            names,
+            wins: vec![vec![0; n]; n],
+            draws: vec![vec![0; n]; n],
+        }
+    }
+
+    /// i と j の対戦結果を加える
+    pub fn add(&mut self, i: usize, j: usize, wins_i: usize, wins_j: usize, draws: usize) {
+        self.wins[i][j] += wins_i;
+        self.wins[j][i] += wins_j;
+        self.draws[i][j] += draws;
+        self.draws[j][i] += draws;
+    }
+
+    /// i と j の対戦数
+    pub fn games(&self, i: usize, j: usize) -> usize {
+        self.wins[i][j] + self.wins[j][i] + self.draws[i][j]
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rating {
+    pub name: String,
+    /// 平均が 0 になるようにした Elo レーティング
+    pub elo: f64,
+    /// 95% 信頼区間の幅（`elo ± elo_error`）
+    pub elo_error: f64,
+}
+
+/// 1 点差あたりの Elo（`400 / ln(10)`）
+const ELO_PER_NATURAL: f64 = 173.717_792_761_245_1;
+
+/// Bradley–Terry モデルの強さを MM アルゴリズムで推定し、Elo に換算する
+/// - 引き分けは両者 0.5 勝とみなす
+/// - 全勝・全敗でも発散しないように、対戦のある組には 1 回ずつ仮想的な引き分けを加える
+/// - 信頼区間は、Fisher 情報量の対角成分だけを使った近似
+pub fn bradley_terry(crosstable: &Crosstable) -> Vec<Rating> {
+    let n = crosstable.names.len();
+
+    // games[i][j], score[i]（仮想的な引き分けを含む）
+    let mut games = vec![vec![0.0; n]; n];
+    let mut score = vec![0.0; n];
+    for i in 0..n {
+        for j in 0..n {
+            if i == j || crosstable.games(i, j) == 0 {
+                continue;
+            }
+            games[i][j] = crosstable.games(i, j) as f64 + 1.0;
+            score[i] += crosstable.wins[i][j] as f64 + 0.5 * crosstable.draws[i][j] as f64 + 0.5;
+        }
+    }
+
+    let mut strength = vec![1.0; n];
+    for _ in 0..10000 {
+        let mut next = strength.clone();
+        for i in 0..n {
+            let denominator: f64 = (0..n)
+                .filter(|&j| games[i][j] > 0.0)
+                .map(|j| games[i][j] / (strength[i] + strength[j]))
+                .sum();
+            if denominator > 0.0 {
+                next[i] = score[i] / denominator;
+            }
+        }
+        // 幾何平均が 1 になるように正規化
+        let log_mean = next.iter().map(|s: &f64| s.ln()).sum::<f64>() / n as f64;
+        for s in next.iter_mut() {
+            *s /= log_mean.exp();
+        }
+
+        let diff = strength
+            .iter()
+            .zip(&next)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        strength = next;
+        if diff < 1e-10 {
+            break;
+        }
+    }
+
+    (0..n)
+        .map(|i| {
+            let information: f64 = (0..n)
+                .filter(|&j| games[i][j] > 0.0)
+                .map(|j| {
+                    let p = strength[i] / (strength[i] + strength[j]);
+                    games[i][j] * p * (1.0 - p)
+                })
+                .sum();
+            Rating {
+                name: crosstable.names[i].clone(),
+                elo: strength[i].ln() * ELO_PER_NATURAL,
+                elo_error: if information > 0.0 {
+                    1.96 / information.sqrt() * ELO_PER_NATURAL
+                } else {
+                    f64::INFINITY
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bradley_terry() {
+        let mut crosstable = Crosstable::new(vec!["A".into(), "B".into(), "C".into()]);
+        crosstable.add(0, 1, 30, 10, 0);
+        crosstable.add(0, 2, 35, 5, 0);
+        crosstable.add(1, 2, 25, 15, 0);
+        assert_eq!(crosstable.games(0, 1), 40);
+
+        let ratings = bradley_terry(&crosstable);
+        assert!(ratings[0].elo > ratings[1].elo);
+        assert!(ratings[1].elo > ratings[2].elo);
+        assert!(ratings.iter().map(|r| r.elo).sum::<f64>().abs() < 1e-6);
+        assert!(ratings.iter().all(|r| r.elo_error.is_finite()));
+
+        // 全勝でも発散しない
+        let mut crosstable = Crosstable::new(vec!["A".into(), "B".into()]);
+        crosstable.add(0, 1, 10, 0, 0);
+        let ratings = bradley_terry(&crosstable);
+        assert!(ratings[0].elo.is_finite());
+        assert!(ratings[0].elo > ratings[1].elo);
+    }
+}
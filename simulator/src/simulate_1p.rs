@@ -7,9 +7,10 @@ use chrono::{DateTime, Utc};
 use cpu::bot::*;
 use logger::Logger;
 use puyoai::{
-    color::Color, decision::Decision, field::CoreField, kumipuyo::Kumipuyo, puyop::make_puyop_url,
-    serde_def::DecisionDef,
+    color::Color, decision::Decision, es_field::EsCoreField, field::CoreField, kumipuyo::Kumipuyo,
+    puyop::make_puyop_url, serde_def::DecisionDef,
 };
+use rand::{rngs::StdRng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
@@ -22,12 +23,13 @@ pub fn simulate_1p(
     max_tumos: usize,
     haipuyo_margin: Option<usize>, // Noneならランダムに、Someならその番号の配ぷよを使う
     required_chain_score: Option<usize>, // この得点以上の連鎖が打たれたら終了
+    seed: Option<u64>,             // 配ぷよをランダムに選ぶときのシード（棋譜に記録される）
 ) -> Result<SimulateResult1P, std::io::Error> {
     logger.print(format!("> AI: {} ({:3}手読み)\n", ai.name(), visible_tumos))?;
 
     // TODO: フレームを更新する
     let seq = match haipuyo_margin {
-        None => HaipuyoDetector::random_haipuyo(),
+        None => HaipuyoDetector::random_haipuyo_with_rng(&mut new_rng(seed)),
         Some(margin) => HaipuyoDetector::retrieve_haipuyo(margin % TUMO_PATTERN),
     };
     let mut player_state = PlayerState::initial_state(vec![], Some(seq.clone()));
@@ -39,8 +41,11 @@ pub fn simulate_1p(
     for tumo_index in 1..=max_tumos {
         // AI に考えさせる
         player_state.set_seq(visible_tumos);
-        // TODO: 引数で `think_frame` を渡す？
-        let ai_decision = ai.think(player_state.clone(), None, None);
+        // ツモが（3 列目にそのまま置いた場合に）接地するまでの間に思考する
+        let think_frame = player_state
+            .field
+            .es_frames_to_drop_next(&Decision::new(3, 0));
+        let ai_decision = ai.think(player_state.clone(), None, Some(think_frame));
         ai_decisions.push(ai_decision.clone());
         decisions.push(ai_decision.decisions[0].clone());
 
@@ -75,7 +80,7 @@ pub fn simulate_1p(
         player_state.tumo_index += 1;
     }
 
-    let ret = SimulateResult1P::new(score, visible_tumos, &seq, &decisions, &ai_decisions);
+    let ret = SimulateResult1P::new(score, visible_tumos, seed, &seq, &decisions, &ai_decisions);
     logger.print(ret.url.clone())?;
 
     Ok(ret)
@@ -88,6 +93,22 @@ pub struct JsonDecision {
     log_output: String,
     #[serde_as(as = "Vec<DecisionDef>")]
     decisions: Vec<Decision>,
+    #[serde(default)]
+    pub reason: DecisionReason,
+    #[serde(default)]
+    pub statistics: Option<SearchStatistics>,
+}
+
+impl From<&AIDecision> for JsonDecision {
+    fn from(ai_decision: &AIDecision) -> Self {
+        JsonDecision {
+            think_ms: ai_decision.elapsed.as_millis(),
+            log_output: ai_decision.log_output.clone(),
+            decisions: ai_decision.decisions.clone(),
+            reason: ai_decision.reason.clone(),
+            statistics: ai_decision.statistics.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -95,6 +116,8 @@ pub struct SimulateResult1P {
     date: DateTime<Utc>,
     pub score: usize,
     visible_tumos: usize,
+    #[serde(default)]
+    pub seed: Option<u64>,
     tumos: Vec<String>, // ["RR", "YG", ...]
     pub json_decisions: Vec<JsonDecision>,
     url: String,
@@ -104,28 +127,20 @@ impl SimulateResult1P {
     fn new(
         score: usize,
         visible_tumos: usize,
+        seed: Option<u64>,
         seq: &Vec<Kumipuyo>,
         decisions: &Vec<Decision>,
         ai_decisions: &Vec<AIDecision>,
     ) -> Self {
         let url = make_puyop_url(&CoreField::new(), seq, decisions);
         let tumos = convert_kumipuyo_seq(&seq);
-        let json_decisions = {
-            let mut json_decisions = vec![];
-            for ai_decision in ai_decisions {
-                json_decisions.push(JsonDecision {
-                    think_ms: ai_decision.elapsed.as_millis(),
-                    log_output: ai_decision.log_output.clone(),
-                    decisions: ai_decision.decisions.clone(),
-                });
-            }
-            json_decisions
-        };
+        let json_decisions = ai_decisions.iter().map(JsonDecision::from).collect();
 
         SimulateResult1P {
             date: Utc::now(),
             score,
             visible_tumos,
+            seed,
             tumos,
             json_decisions,
             url,
@@ -148,3 +163,11 @@ impl SimulateResult1P {
         Ok(())
     }
 }
+
+/// シードが指定されていればそれを使う乱数（シミュレータ間で共通）
+pub(crate) fn new_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
@@ -1,10 +1,3 @@
-/*
-   TODO
-   - 同フレームのやつが2個（1Pと2P）並んでたら、後にある方を処理する前に盤面の更新を行わないこと（同時なので）
-   - 同時に死んだら引き分けにするべき
-   - 全消しの考慮
-*/
-
 use std::{
     collections::BinaryHeap,
     fs::{create_dir_all, File},
@@ -12,10 +5,15 @@ use std::{
 };
 
 use chrono::{DateTime, Utc};
-use cpu::bot::{PlayerState, AI};
+use cpu::bot::{OpponentView, PlayerState, AI};
 use logger::Logger;
 use puyoai::{
-    decision::Decision, es_field::EsCoreField, es_frame, field::CoreField, field_bit::FieldBit,
+    decision::Decision,
+    es_field::EsCoreField,
+    es_frame::{self, FrameModel},
+    field::CoreField,
+    field_bit::FieldBit,
+    kumipuyo::Kumipuyo,
     rensa_tracker::RensaNonTracker,
 };
 use serde::{Deserialize, Serialize};
@@ -23,10 +21,11 @@ use serde::{Deserialize, Serialize};
 use super::{
     convert::{convert_core_field, convert_kumipuyo_seq},
     haipuyo_detector::*,
+    simulate_1p::{new_rng, JsonDecision},
 };
 
-// TODO: マージンの実装
-const OJAMA_PUYO_RATE: usize = 70;
+/// 引き分けがこの回数続いたら打ち切る（同じ AI 同士だと延々と引き分けになりうるので）
+const MAX_CONSECUTIVE_DRAWS: usize = 10;
 
 pub fn simulate_2p(
     logger: &mut Box<dyn Logger>,
@@ -36,255 +35,435 @@ pub fn simulate_2p(
     visible_tumos: usize,
     // FIXME: 序盤数手が同じになってしまう
     haipuyo_margin: Option<usize>, // Noneならランダムに、Someならその番号から順番に使う
+    frame_model: &FrameModel,      // 操作・連鎖・おじゃまにかかるフレーム数
+    // 思考時間 1 ms あたり何フレーム経過させるか（None なら思考時間は無視する。実時間なら 0.06）
+    frames_per_ms: Option<f64>,
+    seed: Option<u64>, // 配ぷよをランダムに選ぶときのシード（棋譜に記録される）
 ) -> Result<SimulateResult2P, std::io::Error> {
-    // お互いの勝利数
+    // お互いの勝利数と、引き分けの数
     let mut win_count_1p: usize = 0;
     let mut win_count_2p: usize = 0;
+    let mut draw_count: usize = 0;
+    let mut consecutive_draws: usize = 0;
 
     // 各試合の詳細
     let mut json_matches: Vec<JsonMatch> = vec![];
 
+    let mut rng = new_rng(seed);
+
     while win_count_1p < win_goal && win_count_2p < win_goal {
-        // 配ぷよを決める
+        // 配ぷよを決める（引き分けも含めて、試合ごとに次の番号に進める）
+        let match_index = json_matches.len();
         let seq = match haipuyo_margin {
-            None => HaipuyoDetector::random_haipuyo(),
-            Some(margin) => HaipuyoDetector::retrieve_haipuyo(
-                (margin + win_count_1p + win_count_2p) % TUMO_PATTERN,
-            ),
-        };
-
-        // 各プレイヤーの状態
-        let mut player_state_1p = PlayerState::initial_state(vec![], Some(seq.clone()));
-        let mut player_state_2p = PlayerState::initial_state(vec![], Some(seq.clone()));
-        player_state_1p.set_seq(visible_tumos);
-        player_state_2p.set_seq(visible_tumos);
-
-        // この試合で起きたイベント
-        let mut json_events: Vec<JsonEvent> = vec![];
-        // どっちが勝ったか
-        let winner_player: Option<Player>;
-
-        // 初期盤面をpush
-        json_events.push(JsonEvent {
-            frame: 0,
-            json_state_1p: player_state_1p.clone().into(),
-            json_state_2p: player_state_2p.clone().into(),
-        });
-
-        // 処理すべき各イベント
-        let mut events = BinaryHeap::new();
-        events.push(Event::new(0, Player::One, None, false));
-        events.push(Event::new(0, Player::Two, None, false));
-
-        // どちらかが死ぬまで続ける
-        'battle: loop {
-            let event = match events.pop() {
-                Some(event) => event,
-                None => unreachable!(),
-            };
-
-            let (player_state_myself, player_state_opponent) = if event.player == Player::One {
-                (&mut player_state_1p, &mut player_state_2p)
-            } else {
-                (&mut player_state_2p, &mut player_state_1p)
-            };
-            let ai = if event.player == Player::One {
-                ai_1p
-            } else {
-                ai_2p
-            };
-
-            // `json_events` を更新
-            // TODO: 関数化できるならそうしたい
-            macro_rules! push_json_event {
-                ($frame:expr, $player:expr) => {
-                    json_events.push(JsonEvent {
-                        frame: $frame,
-                        json_state_1p: match $player {
-                            Player::One => player_state_myself.clone(),
-                            Player::Two => player_state_opponent.clone(),
-                        }
-                        .into(),
-                        json_state_2p: match $player {
-                            Player::One => player_state_opponent.clone(),
-                            Player::Two => player_state_myself.clone(),
-                        }
-                        .into(),
-                    });
-                };
-            }
-
-            // 置く場所がすでに決まっている or 連鎖中
-            if let Some(decision) = event.decision {
-                // 連鎖中でないなら、ぷよを置いて `PlayerState` を更新する
-                if player_state_myself.current_chain == 0 {
-                    player_state_myself.drop_kumipuyo(&decision);
-                    player_state_myself.tumo_index += 1;
-                    player_state_myself.set_seq(visible_tumos);
-                    player_state_myself.frame = event.frame;
-                    // NOTE: 1 連鎖目が vanish で消えるのでそこの帳尻合わせ
-                    player_state_myself.current_chain += 1;
-                }
-
-                // ぷよを置いた後の盤面を push
-                push_json_event!(event.frame, event.player);
-
-                // 連鎖が発生したら、盤面・フレーム・おじゃまを更新
-                let (chain_score, chain_frame) = vanish_single_chain(
-                    &mut player_state_myself.field,
-                    player_state_myself.current_chain,
-                );
-                if chain_score != 0 {
-                    // おじゃまを 1 連鎖分処理
-                    player_state_myself.carry_over += chain_score;
-                    let mut ojama = player_state_myself.carry_over / OJAMA_PUYO_RATE;
-                    player_state_myself.carry_over %= OJAMA_PUYO_RATE;
-
-                    // 相殺に当てる
-                    if ojama > 0 && player_state_myself.fixed_ojama > 0 {
-                        let pay = ojama.min(player_state_myself.fixed_ojama);
-                        ojama -= pay;
-                        player_state_myself.fixed_ojama -= pay;
-                    }
-                    if ojama > 0 && player_state_myself.pending_ojama > 0 {
-                        let pay = ojama.min(player_state_myself.pending_ojama);
-                        ojama -= pay;
-                        player_state_myself.pending_ojama -= pay;
-                    }
-
-                    // 余った分は相手に送る
-                    if ojama > 0 {
-                        player_state_opponent.pending_ojama += ojama;
-                    }
-
-                    // 自身の状態を更新
-                    player_state_myself.score += chain_score;
-                    player_state_myself.frame += chain_frame;
-                    player_state_myself.current_chain += 1;
-
-                    events.push(Event::new(
-                        player_state_myself.frame,
-                        event.player,
-                        Some(decision),
-                        false,
-                    ));
-
-                    continue;
-                }
-            }
-
-            // 連鎖が発生していないので 0 に戻す
-            player_state_myself.current_chain = 0;
-
-            // おじゃまを降らせる
-            if !event.force_think && player_state_myself.fixed_ojama > 0 {
-                // 30個以上だったら30個、そうでないならその分降らせる
-                let ojama = if player_state_myself.fixed_ojama >= 30 {
-                    30
-                } else {
-                    player_state_myself.fixed_ojama
-                };
-                player_state_myself.fixed_ojama -= ojama;
-
-                let ojama_drop_frame = player_state_myself.field.es_drop_ojama(
-                    ojama,
-                    Some(
-                        ((win_count_1p
-                            + win_count_2p
-                            + player_state_myself.score
-                            + player_state_opponent.score
-                            + ojama)
-                            & 0b11111111) as u8,
-                    ),
-                );
-
-                // フレームを更新
-                // TODO: おじゃまの降る位置がかなり早い段階（降り始めたタイミング）で凝視できるようになってしまっている
-                player_state_myself.frame += ojama_drop_frame;
-
-                events.push(Event::new(
-                    player_state_myself.frame,
-                    event.player,
-                    None,
-                    true, // おじゃまが降ったので、次は必ず操作を行う
-                ));
-
-                // おじゃまが降った後の盤面を push
-                push_json_event!(event.frame, event.player);
-
-                continue;
-            }
-
-            // ぷよを置いて / おじゃまが降って、自陣が死んでたら終了
-            // TODO: 同時に死ぬ場合がありうる
-            if player_state_myself.field.is_dead() {
-                winner_player = Some(event.player.opponent());
-                break 'battle;
-            }
-
-            // 思考する前に相手の予告ぷよを確定させる
-            if player_state_opponent.pending_ojama > 0 {
-                player_state_opponent.fixed_ojama += player_state_opponent.pending_ojama;
-                player_state_opponent.pending_ojama = 0;
+            None => HaipuyoDetector::random_haipuyo_with_rng(&mut rng),
+            Some(margin) => {
+                HaipuyoDetector::retrieve_haipuyo((margin + match_index) % TUMO_PATTERN)
             }
+        };
 
-            // AIで思考する
-            // TODO: 引数で `think_frame` を渡す？
-            let ai_decision = ai.think(
-                player_state_myself.clone(),
-                Some(player_state_opponent.clone()),
-                None,
-            );
-            let decision = ai_decision.decisions[0].clone();
-            events.push(Event::new(
-                // そこに置くのに必要なフレーム数を加算
-                event.frame + player_state_myself.field.es_frames_to_drop_next(&decision),
-                event.player,
-                Some(decision),
-                false,
-            ));
-        }
+        let json_match = simulate_match(
+            ai_1p,
+            ai_2p,
+            &seq,
+            visible_tumos,
+            match_index,
+            frame_model,
+            frames_per_ms,
+        );
 
         // 結果を更新
-        match winner_player {
-            Some(player) => match player {
-                Player::One => {
-                    logger.print("1P won! ".into())?;
-                    win_count_1p += 1;
-                }
-                Player::Two => {
-                    logger.print("2P won! ".into())?;
-                    win_count_2p += 1;
-                }
-            },
-            None => unreachable!(),
+        if json_match.draw {
+            logger.print("Draw!   ".into())?;
+            draw_count += 1;
+            consecutive_draws += 1;
+        } else if json_match.won_1p {
+            logger.print("1P won! ".into())?;
+            win_count_1p += 1;
+            consecutive_draws = 0;
+        } else {
+            logger.print("2P won! ".into())?;
+            win_count_2p += 1;
+            consecutive_draws = 0;
         }
+        let last_json_event = json_match.json_events.last().unwrap();
         logger.print(format!(
             "{:3} vs {:3} ({:6} - {:6})\n",
-            win_count_1p, win_count_2p, player_state_1p.score, player_state_2p.score
+            win_count_1p,
+            win_count_2p,
+            last_json_event.json_state_1p.score,
+            last_json_event.json_state_2p.score
         ))?;
 
         // この試合の結果をpush
-        json_matches.push(JsonMatch {
-            won_1p: winner_player.unwrap() == Player::One,
-            tumos: convert_kumipuyo_seq(&seq),
-            json_events,
-        })
+        json_matches.push(json_match);
+
+        if consecutive_draws >= MAX_CONSECUTIVE_DRAWS {
+            logger.print(format!(
+                "Aborted: {} draws in a row\n",
+                MAX_CONSECUTIVE_DRAWS
+            ))?;
+            break;
+        }
     }
 
     logger.print(format!(
-        "Result: {:3} vs {:3}\n",
-        win_count_1p, win_count_2p
+        "Result: {:3} vs {:3} (draw: {})\n",
+        win_count_1p, win_count_2p, draw_count
     ))?;
 
     Ok(SimulateResult2P::new(
         win_count_1p,
         win_count_2p,
+        draw_count,
         visible_tumos,
+        seed,
         json_matches,
     ))
 }
 
+/// 1 試合分をシミュレーションする
+pub fn simulate_match(
+    ai_1p: &Box<dyn AI>,
+    ai_2p: &Box<dyn AI>,
+    seq: &Vec<Kumipuyo>,
+    visible_tumos: usize,
+    match_index: usize,
+    frame_model: &FrameModel,
+    frames_per_ms: Option<f64>,
+) -> JsonMatch {
+    let ais = [ai_1p, ai_2p];
+
+    // 各プレイヤーの状態
+    let mut player_states = [
+        PlayerState::initial_state(vec![], Some(seq.clone())),
+        PlayerState::initial_state(vec![], Some(seq.clone())),
+    ];
+    for player_state in player_states.iter_mut() {
+        player_state.set_seq(visible_tumos);
+    }
+
+    // この試合で起きたイベント（初期盤面を入れておく）
+    let mut json_events: Vec<JsonEvent> = vec![JsonEvent::new(0, &player_states)];
+    // 各プレイヤーの思考結果
+    let mut json_decisions: [Vec<JsonDecision>; 2] = [vec![], vec![]];
+
+    // 処理すべき各イベント
+    let mut events = BinaryHeap::new();
+    events.push(Event::new(0, Player::One, None, false));
+    events.push(Event::new(0, Player::Two, None, false));
+
+    // どちらかが死ぬまで続ける
+    let match_result = loop {
+        // 同じフレームのイベントはまとめて処理する
+        let mut same_frame_events = vec![events.pop().unwrap()];
+        while events
+            .peek()
+            .map_or(false, |event| event.frame == same_frame_events[0].frame)
+        {
+            same_frame_events.push(events.pop().unwrap());
+        }
+        same_frame_events.sort_by_key(|event| event.player.index());
+
+        if let Some(match_result) = process_same_frame_events(
+            same_frame_events,
+            &mut player_states,
+            &ais,
+            &mut events,
+            &mut json_events,
+            &mut json_decisions,
+            visible_tumos,
+            match_index,
+            frame_model,
+            frames_per_ms,
+        ) {
+            break match_result;
+        }
+    };
+
+    let [json_decisions_1p, json_decisions_2p] = json_decisions;
+    JsonMatch {
+        won_1p: match_result == MatchResult::Won(Player::One),
+        draw: match_result == MatchResult::Draw,
+        tumos: convert_kumipuyo_seq(seq),
+        json_events,
+        json_decisions_1p,
+        json_decisions_2p,
+    }
+}
+
+/// 同じフレームのイベントをまとめて処理する（1P と 2P のどちらを先に処理しても結果が変わらないように）
+/// 1. 各プレイヤーの盤面を進める（ぷよを置く・1 連鎖分進める・おじゃまを降らせる）
+/// 2. 連鎖で発生したおじゃまを相殺して、余った分を相手に送る
+/// 3. 操作に移るプレイヤーの死亡判定を行う（同時に死んだら引き分け）
+/// 4. 相手の予告おじゃまを確定させてから思考する
+/// 決着がついたらその結果を返す
+fn process_same_frame_events(
+    same_frame_events: Vec<Event>,
+    player_states: &mut [PlayerState; 2],
+    ais: &[&Box<dyn AI>; 2],
+    events: &mut BinaryHeap<Event>,
+    json_events: &mut Vec<JsonEvent>,
+    json_decisions: &mut [Vec<JsonDecision>; 2],
+    visible_tumos: usize,
+    match_index: usize,
+    frame_model: &FrameModel,
+    frames_per_ms: Option<f64>,
+) -> Option<MatchResult> {
+    let frame = same_frame_events[0].frame;
+
+    // マージンタイムによるレートの変化を反映
+    let ojama_rate = es_frame::ojama_puyo_rate(frame);
+    for player_state in player_states.iter_mut() {
+        player_state.ojama_rate = ojama_rate;
+    }
+
+    // おじゃまの落下位置のシード（処理順に依らないように、処理前の点数を使う）
+    let ojama_seed = match_index + player_states[0].score + player_states[1].score;
+
+    // 1. 盤面を進める
+    let mut ojama_generated = [0; 2];
+    let mut ready_players: Vec<Player> = vec![];
+    for event in &same_frame_events {
+        match advance_player(
+            event,
+            player_states,
+            events,
+            json_events,
+            visible_tumos,
+            ojama_seed,
+            frame_model,
+        ) {
+            Advance::Chain(ojama) => ojama_generated[event.player.index()] = ojama,
+            Advance::OjamaDropped => {}
+            Advance::Ready => ready_players.push(event.player),
+        }
+    }
+
+    // 2. 自分に降る予定のおじゃまと相殺して、余った分を相手に送る
+    let mut ojama_to_send = [0; 2];
+    for player in [Player::One, Player::Two] {
+        let player_state = &mut player_states[player.index()];
+        let mut ojama = ojama_generated[player.index()];
+
+        let pay = ojama.min(player_state.fixed_ojama);
+        ojama -= pay;
+        player_state.fixed_ojama -= pay;
+
+        let pay = ojama.min(player_state.pending_ojama);
+        ojama -= pay;
+        player_state.pending_ojama -= pay;
+
+        ojama_to_send[player.index()] = ojama;
+    }
+    // 同時に送り合った分も相殺する
+    let pay = ojama_to_send[0].min(ojama_to_send[1]);
+    for player in [Player::One, Player::Two] {
+        player_states[player.opponent().index()].pending_ojama +=
+            ojama_to_send[player.index()] - pay;
+    }
+
+    // 3. ぷよを置いて / おじゃまが降って、自陣が死んでたら終了
+    let dead_players = ready_players
+        .iter()
+        .filter(|player| player_states[player.index()].field.is_dead())
+        .collect::<Vec<_>>();
+    match dead_players.as_slice() {
+        [] => {}
+        [player] => return Some(MatchResult::Won(player.opponent())),
+        _ => return Some(MatchResult::Draw),
+    }
+
+    // 4. 思考する前に相手の予告ぷよを確定させる（同時に思考する場合は、両者とも確定させてから思考する）
+    for player in &ready_players {
+        let player_state_opponent = &mut player_states[player.opponent().index()];
+        if player_state_opponent.pending_ojama > 0 {
+            player_state_opponent.fixed_ojama += player_state_opponent.pending_ojama;
+            player_state_opponent.pending_ojama = 0;
+        }
+    }
+
+    // AIで思考する
+    for player in ready_players {
+        let player_state_myself = &player_states[player.index()];
+        let player_state_opponent = &player_states[player.opponent().index()];
+
+        let think_frame = available_think_frame(
+            frame,
+            player_state_myself,
+            player_state_opponent,
+            frame_model,
+        );
+        let ai_decision = ais[player.index()].think(
+            player_state_myself.clone(),
+            Some(OpponentView::from(player_state_opponent)),
+            Some(think_frame),
+        );
+        let decision = ai_decision.decisions[0].clone();
+        json_decisions[player.index()].push(JsonDecision::from(&ai_decision));
+
+        // 思考にかかった時間を加算
+        let think_frames = frames_per_ms.map_or(0, |frames_per_ms| {
+            (ai_decision.elapsed.as_secs_f64() * 1000.0 * frames_per_ms).ceil() as usize
+        });
+
+        events.push(Event::new(
+            // そこに置くのに必要なフレーム数を加算
+            frame
+                + think_frames
+                + player_state_myself
+                    .field
+                    .es_frames_to_drop_next_with_frame_model(&decision, frame_model),
+            player,
+            Some(decision),
+            false,
+        ));
+    }
+
+    None
+}
+
+/// 思考に使えるフレーム数
+/// - 今のツモが（3 列目にそのまま置いた場合に）接地するまで
+/// - 相手が連鎖中なら、その連鎖が終わるまで（それまでおじゃまは確定しないので）
+fn available_think_frame(
+    frame: usize,
+    player_state_myself: &PlayerState,
+    player_state_opponent: &PlayerState,
+    frame_model: &FrameModel,
+) -> usize {
+    let frames_to_land = player_state_myself
+        .field
+        .es_frames_to_drop_next_with_frame_model(&Decision::new(3, 0), frame_model);
+
+    if player_state_opponent.current_chain == 0 {
+        return frames_to_land;
+    }
+
+    // 今の 1 連鎖が終わるまでと、残りの連鎖にかかるフレーム数
+    let mut cf = player_state_opponent.field.clone();
+    let rensa_result = cf
+        .es_simulate_from_middle_with_frame_model(player_state_opponent.current_chain, frame_model);
+    let frames_to_chain_end =
+        player_state_opponent.frame.saturating_sub(frame) + rensa_result.frame;
+
+    frames_to_land.max(frames_to_chain_end)
+}
+
+/// 1 人分のイベントを処理した結果
+enum Advance {
+    /// 連鎖が 1 段進んだ（発生したおじゃまの数。相殺前）
+    Chain(usize),
+    /// おじゃまが降った
+    OjamaDropped,
+    /// 操作に移る
+    Ready,
+}
+
+/// 1 人分のイベントについて盤面を進める（相殺・死亡判定・思考は呼び出し側で行う）
+fn advance_player(
+    event: &Event,
+    player_states: &mut [PlayerState; 2],
+    events: &mut BinaryHeap<Event>,
+    json_events: &mut Vec<JsonEvent>,
+    visible_tumos: usize,
+    ojama_seed: usize,
+    frame_model: &FrameModel,
+) -> Advance {
+    let player = event.player;
+
+    // 置く場所がすでに決まっている or 連鎖中
+    if let Some(decision) = &event.decision {
+        // 連鎖中でないなら、ぷよを置いて `PlayerState` を更新する
+        let player_state = &mut player_states[player.index()];
+        if player_state.current_chain == 0 {
+            player_state.drop_kumipuyo(decision);
+            player_state.tumo_index += 1;
+            player_state.set_seq(visible_tumos);
+            player_state.frame = event.frame;
+            // NOTE: 1 連鎖目が vanish で消えるのでそこの帳尻合わせ
+            player_state.current_chain += 1;
+        }
+
+        // ぷよを置いた後の盤面を push
+        json_events.push(JsonEvent::new(event.frame, player_states));
+
+        // 連鎖が発生したら、盤面・フレーム・おじゃまを更新
+        let player_state = &mut player_states[player.index()];
+        let (chain_score, chain_frame) = vanish_single_chain(
+            &mut player_state.field,
+            player_state.current_chain,
+            frame_model,
+        );
+        if chain_score != 0 {
+            // おじゃまを 1 連鎖分計算（相殺は呼び出し側で行う）
+            player_state.carry_over += chain_score;
+            let mut ojama = player_state.carry_over / player_state.ojama_rate;
+            player_state.carry_over %= player_state.ojama_rate;
+
+            // 全消しボーナスは次の連鎖の 1 連鎖目で送る（点数ではなく個数なので、レートに依らない）
+            if player_state.current_chain == 1 && player_state.has_zenkeshi {
+                ojama += es_frame::ZENKESHI_OJAMA;
+                player_state.has_zenkeshi = false;
+            }
+
+            // 自身の状態を更新
+            player_state.score += chain_score;
+            player_state.frame += chain_frame;
+            player_state.current_chain += 1;
+
+            events.push(Event::new(
+                player_state.frame,
+                player,
+                Some(decision.clone()),
+                false,
+            ));
+
+            return Advance::Chain(ojama);
+        }
+    }
+
+    let player_state = &mut player_states[player.index()];
+
+    // 連鎖が終わった時点で盤面が空なら全消し
+    if player_state.current_chain > 1 && player_state.field.is_zenkeshi() {
+        player_state.has_zenkeshi = true;
+    }
+
+    // 連鎖が発生していないので 0 に戻す
+    player_state.current_chain = 0;
+
+    // おじゃまを降らせる
+    if !event.force_think && player_state.fixed_ojama > 0 {
+        // 30個以上だったら30個、そうでないならその分降らせる
+        let ojama = player_state.fixed_ojama.min(30);
+        player_state.fixed_ojama -= ojama;
+
+        let ojama_drop_frame = player_state.field.es_drop_ojama_with_frame_model(
+            ojama,
+            Some(((ojama_seed + ojama) & 0b11111111) as u8),
+            frame_model,
+        );
+
+        // フレームを更新
+        // TODO: おじゃまの降る位置がかなり早い段階（降り始めたタイミング）で凝視できるようになってしまっている
+        player_state.frame += ojama_drop_frame;
+
+        events.push(Event::new(
+            player_state.frame,
+            player,
+            None,
+            true, // おじゃまが降ったので、次は必ず操作を行う
+        ));
+
+        // おじゃまが降った後の盤面を push
+        json_events.push(JsonEvent::new(event.frame, player_states));
+
+        return Advance::OjamaDropped;
+    }
+
+    Advance::Ready
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct JsonState {
     pub tumo_index: usize,
@@ -293,6 +472,8 @@ pub struct JsonState {
     pub ojama_fixed: usize,   // 確定おじゃまぷよ
     pub ojama_ongoing: usize, // 予告おじゃまぷよ
     pub current_chain: usize, // 現在の連鎖数
+    #[serde(default)]
+    pub has_zenkeshi: bool, // 全消しボーナスを持っているか
 }
 
 impl From<PlayerState> for JsonState {
@@ -304,6 +485,7 @@ impl From<PlayerState> for JsonState {
             ojama_fixed: player_state.fixed_ojama,
             ojama_ongoing: player_state.pending_ojama,
             current_chain: player_state.current_chain,
+            has_zenkeshi: player_state.has_zenkeshi,
         }
     }
 }
@@ -315,11 +497,28 @@ pub struct JsonEvent {
     pub json_state_2p: JsonState,
 }
 
+impl JsonEvent {
+    fn new(frame: usize, player_states: &[PlayerState; 2]) -> Self {
+        JsonEvent {
+            frame,
+            json_state_1p: player_states[0].clone().into(),
+            json_state_2p: player_states[1].clone().into(),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct JsonMatch {
     pub won_1p: bool,
+    #[serde(default)]
+    pub draw: bool, // 同時に死んだか（このとき `won_1p` は false）
     pub tumos: Vec<String>,
     pub json_events: Vec<JsonEvent>,
+    // 各プレイヤーの思考結果（ツモ順）
+    #[serde(default)]
+    pub json_decisions_1p: Vec<JsonDecision>,
+    #[serde(default)]
+    pub json_decisions_2p: Vec<JsonDecision>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -327,22 +526,30 @@ pub struct SimulateResult2P {
     pub date: DateTime<Utc>,
     pub win_count_1p: usize,
     pub win_count_2p: usize,
+    #[serde(default)]
+    pub draw_count: usize,
     pub visible_tumos: usize,
+    #[serde(default)]
+    pub seed: Option<u64>,
     pub json_matches: Vec<JsonMatch>,
 }
 
 impl SimulateResult2P {
-    fn new(
+    pub fn new(
         win_count_1p: usize,
         win_count_2p: usize,
+        draw_count: usize,
         visible_tumos: usize,
+        seed: Option<u64>,
         json_matches: Vec<JsonMatch>,
     ) -> Self {
         SimulateResult2P {
             date: Utc::now(),
             win_count_1p,
             win_count_2p,
+            draw_count,
             visible_tumos,
+            seed,
             json_matches,
         }
     }
@@ -372,7 +579,7 @@ impl SimulateResult2P {
     }
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Player {
     One,
     Two,
@@ -385,6 +592,19 @@ impl Player {
             Player::Two => Player::One,
         }
     }
+
+    fn index(&self) -> usize {
+        match *self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MatchResult {
+    Won(Player),
+    Draw,
 }
 
 struct Event {
@@ -428,7 +648,11 @@ impl PartialEq for Event {
 impl Eq for Event {}
 
 /// 1 連鎖分進めて (点数, フレーム数) を返す
-fn vanish_single_chain(cf: &mut CoreField, current_chain: usize) -> (usize, usize) {
+fn vanish_single_chain(
+    cf: &mut CoreField,
+    current_chain: usize,
+    frame_model: &FrameModel,
+) -> (usize, usize) {
     let escaped = cf.field_mut().escape_invisible();
     let mut erased = unsafe { FieldBit::uninitialized() };
     let chain_score = cf.field().vanish(
@@ -448,7 +672,7 @@ fn vanish_single_chain(cf: &mut CoreField, current_chain: usize) -> (usize, usiz
             .drop_after_vanish(erased, &mut RensaNonTracker::new())
     };
     cf.update_height();
-    let chain_frame = es_frame::FRAMES_CHAIN[max_drops];
+    let chain_frame = frame_model.frames_chain[max_drops];
 
     cf.field_mut().recover_invisible(&escaped);
     (chain_score, chain_frame)
@@ -456,8 +680,290 @@ fn vanish_single_chain(cf: &mut CoreField, current_chain: usize) -> (usize, usiz
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use cpu::bot::AIDecision;
+    use puyoai::color::PuyoColor;
+
     use super::*;
 
+    /// 常に 3 列目に縦置きする AI
+    struct ThirdColumnAI {
+        /// 思考にかかったことにする時間
+        elapsed: Duration,
+    }
+
+    impl AI for ThirdColumnAI {
+        fn new() -> Self {
+            ThirdColumnAI {
+                elapsed: Duration::ZERO,
+            }
+        }
+
+        fn name(&self) -> &'static str {
+            "ThirdColumnAI"
+        }
+
+        fn think(
+            &self,
+            _player_state_1p: PlayerState,
+            _player_state_2p: Option<OpponentView>,
+            _think_frame: Option<usize>,
+        ) -> AIDecision {
+            AIDecision::from_decision(&Decision::new(3, 0), "".into(), self.elapsed)
+        }
+    }
+
+    /// 赤ぷよを 3 列目に置くと 1 連鎖（40 点）が起きる状態
+    fn player_state_before_chain(carry_over: usize) -> PlayerState {
+        let kumipuyo = Kumipuyo::new(PuyoColor::RED, PuyoColor::RED);
+        let mut player_state = PlayerState::initial_state(
+            vec![kumipuyo.clone()],
+            Some(vec![kumipuyo.clone(), kumipuyo]),
+        );
+        player_state.field = CoreField::from_str("  RR  ");
+        player_state.carry_over = carry_over;
+        player_state
+    }
+
+    fn process(
+        player_states: &mut [PlayerState; 2],
+        same_frame_events: Vec<Event>,
+        events: &mut BinaryHeap<Event>,
+    ) -> Option<MatchResult> {
+        let ai: Box<dyn AI> = Box::new(ThirdColumnAI::new());
+        process_same_frame_events(
+            same_frame_events,
+            player_states,
+            &[&ai, &ai],
+            events,
+            &mut vec![],
+            &mut [vec![], vec![]],
+            1,
+            0,
+            &FrameModel::default(),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_think_time_consumes_frames() {
+        let frame_model = FrameModel::default();
+        let ai: Box<dyn AI> = Box::new(ThirdColumnAI {
+            elapsed: Duration::from_millis(100),
+        });
+
+        for (frames_per_ms, think_frames) in [(None, 0), (Some(0.06), 6)] {
+            let mut player_states = [PlayerState::zero(), PlayerState::zero()];
+            let mut events = BinaryHeap::new();
+            let result = process_same_frame_events(
+                vec![Event::new(100, Player::One, None, false)],
+                &mut player_states,
+                &[&ai, &ai],
+                &mut events,
+                &mut vec![],
+                &mut [vec![], vec![]],
+                1,
+                0,
+                &frame_model,
+                frames_per_ms,
+            );
+            assert_eq!(result, None);
+            assert_eq!(
+                events.pop().unwrap().frame,
+                100 + think_frames + frame_model.frames_grounding[0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_offset_on_same_frame() {
+        let fire = |player| Event::new(100, player, Some(Decision::new(3, 2)), false);
+
+        // 同じ量を送り合ったら打ち消し合う
+        let mut player_states = [
+            player_state_before_chain(660),
+            player_state_before_chain(660),
+        ];
+        let mut events = BinaryHeap::new();
+        let result = process(
+            &mut player_states,
+            vec![fire(Player::One), fire(Player::Two)],
+            &mut events,
+        );
+        assert_eq!(result, None);
+        assert_eq!(events.len(), 2);
+        for player_state in &player_states {
+            assert_eq!(player_state.score, 40);
+            assert_eq!(player_state.carry_over, 0);
+            assert_eq!(player_state.fixed_ojama, 0);
+            assert_eq!(player_state.pending_ojama, 0);
+        }
+
+        // 1P: 10 個発生して、確定していた 3 個と相殺 → 7 個
+        // 2P: 6 個発生
+        // → 送り合った分を相殺して、2P に 1 個
+        let mut player_states = [
+            player_state_before_chain(660),
+            player_state_before_chain(380),
+        ];
+        player_states[0].fixed_ojama = 3;
+        let mut events = BinaryHeap::new();
+        let result = process(
+            &mut player_states,
+            vec![fire(Player::One), fire(Player::Two)],
+            &mut events,
+        );
+        assert_eq!(result, None);
+        assert_eq!(player_states[0].fixed_ojama, 0);
+        assert_eq!(player_states[0].pending_ojama, 0);
+        assert_eq!(player_states[1].fixed_ojama, 0);
+        assert_eq!(player_states[1].pending_ojama, 1);
+    }
+
+    #[test]
+    fn test_zenkeshi_bonus_under_reduced_rate() {
+        // マージンタイムでレートが下がっていても、全消しボーナスは 30 個
+        let frame = es_frame::FRAMES_MARGIN_TIME + es_frame::FRAMES_MARGIN_INTERVAL * 3;
+        let ojama_rate = es_frame::ojama_puyo_rate(frame);
+        assert!(ojama_rate < es_frame::OJAMA_PUYO_RATES[0]);
+
+        let mut player_states = [player_state_before_chain(0), PlayerState::zero()];
+        player_states[0].has_zenkeshi = true;
+        let result = process(
+            &mut player_states,
+            vec![Event::new(
+                frame,
+                Player::One,
+                Some(Decision::new(3, 2)),
+                false,
+            )],
+            &mut BinaryHeap::new(),
+        );
+        assert_eq!(result, None);
+        assert!(!player_states[0].has_zenkeshi);
+        assert_eq!(player_states[0].carry_over, 40 % ojama_rate);
+        assert_eq!(
+            player_states[1].pending_ojama,
+            40 / ojama_rate + es_frame::ZENKESHI_OJAMA
+        );
+    }
+
+    #[test]
+    fn test_commit_ojama_on_same_frame() {
+        // 同時に操作に移る場合は、両者の予告おじゃまを確定させてから思考する
+        let mut player_states = [PlayerState::zero(), PlayerState::zero()];
+        player_states[0].pending_ojama = 5;
+        player_states[1].pending_ojama = 4;
+        let mut events = BinaryHeap::new();
+        let result = process(
+            &mut player_states,
+            vec![
+                Event::new(100, Player::One, None, true),
+                Event::new(100, Player::Two, None, true),
+            ],
+            &mut events,
+        );
+        assert_eq!(result, None);
+        assert_eq!(events.len(), 2);
+        assert_eq!(player_states[0].fixed_ojama, 5);
+        assert_eq!(player_states[0].pending_ojama, 0);
+        assert_eq!(player_states[1].fixed_ojama, 4);
+        assert_eq!(player_states[1].pending_ojama, 0);
+    }
+
+    #[test]
+    fn test_death_on_same_frame() {
+        let ready = |player| Event::new(100, player, None, false);
+        let dead_field = CoreField::from_str(&"  O   ".repeat(12));
+
+        // 同時に死んだら引き分け
+        let mut player_states = [PlayerState::zero(), PlayerState::zero()];
+        player_states[0].field = dead_field.clone();
+        player_states[1].field = dead_field.clone();
+        let result = process(
+            &mut player_states,
+            vec![ready(Player::One), ready(Player::Two)],
+            &mut BinaryHeap::new(),
+        );
+        assert_eq!(result, Some(MatchResult::Draw));
+
+        // 片方だけ死んだら、もう片方の勝ち
+        let mut player_states = [PlayerState::zero(), PlayerState::zero()];
+        player_states[0].field = dead_field;
+        let result = process(
+            &mut player_states,
+            vec![ready(Player::One), ready(Player::Two)],
+            &mut BinaryHeap::new(),
+        );
+        assert_eq!(result, Some(MatchResult::Won(Player::Two)));
+    }
+
+    #[test]
+    fn test_simulate_match_mirrored() {
+        // 同じ AI・同じ配ぷよなら、最後まで同じ操作になるので引き分け
+        let ai: Box<dyn AI> = Box::new(ThirdColumnAI::new());
+        let seq = HaipuyoDetector::retrieve_haipuyo(0);
+        let json_match = simulate_match(&ai, &ai, &seq, 2, 0, &FrameModel::default(), None);
+
+        assert!(json_match.draw);
+        assert!(!json_match.won_1p);
+        // 連鎖が起きても、送り合ったおじゃまは打ち消し合う
+        for json_event in &json_match.json_events {
+            for json_state in [&json_event.json_state_1p, &json_event.json_state_2p] {
+                assert_eq!(json_state.ojama_fixed, 0);
+                assert_eq!(json_state.ojama_ongoing, 0);
+            }
+        }
+        let last_json_event = json_match.json_events.last().unwrap();
+        assert_eq!(
+            last_json_event.json_state_1p.field,
+            last_json_event.json_state_2p.field
+        );
+        // 思考結果も同じ回数だけ記録される
+        assert!(!json_match.json_decisions_1p.is_empty());
+        assert_eq!(
+            json_match.json_decisions_1p.len(),
+            json_match.json_decisions_2p.len()
+        );
+    }
+
+    #[test]
+    fn test_available_think_frame() {
+        let frame_model = FrameModel::default();
+        let player_state_myself = PlayerState::zero();
+
+        // 相手が連鎖していなければ、ツモが接地するまで
+        let player_state_opponent = PlayerState::zero();
+        assert_eq!(
+            available_think_frame(
+                100,
+                &player_state_myself,
+                &player_state_opponent,
+                &frame_model
+            ),
+            frame_model.frames_grounding[0]
+        );
+
+        // 相手が連鎖中なら、その連鎖が終わるまで
+        let mut player_state_opponent = PlayerState::zero();
+        player_state_opponent.field = CoreField::from_str(concat!(
+            "R.....", // 2
+            "RRRBBB"  // 1
+        ));
+        player_state_opponent.frame = 120;
+        player_state_opponent.current_chain = 2;
+        assert_eq!(
+            available_think_frame(
+                100,
+                &player_state_myself,
+                &player_state_opponent,
+                &frame_model
+            ),
+            20 + frame_model.frames_chain[0]
+        );
+    }
+
     #[test]
     fn test_vanish_single_chain() {
         let mut cf = CoreField::from_str(concat!(
@@ -473,7 +979,7 @@ mod tests {
             "RRBYYG"  // 1
         ));
 
-        let (score, frame) = vanish_single_chain(&mut cf, 1);
+        let (score, frame) = vanish_single_chain(&mut cf, 1, &FrameModel::default());
         assert_eq!(score, 180);
         assert_eq!(frame, 80);
 
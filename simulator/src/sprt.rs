@@ -0,0 +1,140 @@
+//! 2 つの AI の強さの差を、逐次確率比検定 (SPRT) で判定する
+//! - 同じ配ぷよで先手・後手を入れ替えた 2 試合を 1 組とし、組ごとの得点（0, 0.5, ..., 2）の分布から
+//!   正規近似の一般化 SPRT で対数尤度比 (LLR) を求める
+
+use serde::{Deserialize, Serialize};
+
+/// 分散が 0 にならないように、各得点に足しておく回数
+const PSEUDO_COUNT: f64 = 1e-3;
+
+/// 検定の結果
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SprtResult {
+    /// まだ判定できない
+    Continue,
+    /// 強さの差は `elo0` 以下（改善していない）
+    AcceptH0,
+    /// 強さの差は `elo1` 以上（改善している）
+    AcceptH1,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sprt {
+    /// 帰無仮説の Elo の差
+    pub elo0: f64,
+    /// 対立仮説の Elo の差
+    pub elo1: f64,
+    /// 第一種の過誤の確率
+    pub alpha: f64,
+    /// 第二種の過誤の確率
+    pub beta: f64,
+    /// pairs[k] := 組の得点が `k / 2` だった回数（k = 0, 1, ..., 4）
+    pub pairs: [usize; 5],
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Self {
+        Sprt {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+            pairs: [0; 5],
+        }
+    }
+
+    /// 1 組分の結果を加える（`half_points` := 組の得点の 2 倍。勝ちを 2、引き分けを 1 として 2 試合分を足したもの）
+    pub fn add_pair(&mut self, half_points: usize) {
+        self.pairs[half_points] += 1;
+    }
+
+    /// 組の数
+    pub fn pair_count(&self) -> usize {
+        self.pairs.iter().sum()
+    }
+
+    /// 1 試合あたりの得点率
+    pub fn score_rate(&self) -> f64 {
+        let count = self.pair_count();
+        if count == 0 {
+            return 0.5;
+        }
+        let half_points: usize = self.pairs.iter().enumerate().map(|(k, &n)| k * n).sum();
+        half_points as f64 / (4 * count) as f64
+    }
+
+    /// (下限, 上限)。LLR が下限を下回れば H0、上限を上回れば H1 を採択する
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// 対数尤度比
+    pub fn llr(&self) -> f64 {
+        if self.pair_count() == 0 {
+            return 0.0;
+        }
+
+        let counts = self.pairs.map(|n| n as f64 + PSEUDO_COUNT);
+        let total: f64 = counts.iter().sum();
+        let score = |k: usize| k as f64 / 4.0;
+        let mean = (0..5).map(|k| counts[k] * score(k)).sum::<f64>() / total;
+        let variance = (0..5)
+            .map(|k| counts[k] * (score(k) - mean).powi(2))
+            .sum::<f64>()
+            / total;
+
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        total * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn result(&self) -> SprtResult {
+        let llr = self.llr();
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            SprtResult::AcceptH0
+        } else if llr >= upper {
+            SprtResult::AcceptH1
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+/// Elo の差が `elo` のときの期待得点率
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprt() {
+        let mut sprt = Sprt::new(0.0, 10.0, 0.05, 0.05);
+        assert_eq!(sprt.result(), SprtResult::Continue);
+        assert_eq!(sprt.llr(), 0.0);
+
+        // 互角なら H0
+        for _ in 0..2000 {
+            for half_points in [0, 1, 2, 2, 3, 4] {
+                sprt.add_pair(half_points);
+            }
+        }
+        assert!((sprt.score_rate() - 0.5).abs() < 1e-9);
+        assert_eq!(sprt.result(), SprtResult::AcceptH0);
+
+        // 明らかに強ければ H1
+        let mut sprt = Sprt::new(0.0, 10.0, 0.05, 0.05);
+        for _ in 0..100 {
+            for half_points in [2, 3, 4, 4] {
+                sprt.add_pair(half_points);
+            }
+        }
+        assert_eq!(sprt.result(), SprtResult::AcceptH1);
+    }
+}